@@ -0,0 +1,109 @@
+#[cfg(target_os = "linux")]
+use std::num::NonZeroU32;
+
+#[cfg(target_os = "linux")]
+use baseview::{
+    Application, Event, EventStatus, MouseEvent, PhySize, Size, Window, WindowEvent, WindowHandler,
+    WindowOpenOptions, WindowScalePolicy,
+};
+
+/// Clicking in any of the windows opens another one. The application exits once all of them have
+/// been closed.
+#[cfg(target_os = "linux")]
+struct AppWindowHandler {
+    name: String,
+    color: u32,
+    _ctx: softbuffer::Context,
+    surface: softbuffer::Surface,
+    current_size: PhySize,
+    damaged: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl AppWindowHandler {
+    fn new(window: &mut Window, name: String, color: u32) -> Self {
+        let ctx = unsafe { softbuffer::Context::new(window) }.unwrap();
+        let mut surface = unsafe { softbuffer::Surface::new(&ctx, window) }.unwrap();
+        surface.resize(NonZeroU32::new(256).unwrap(), NonZeroU32::new(256).unwrap()).unwrap();
+
+        // TODO: no way to query physical size initially?
+        Self {
+            name,
+            color,
+            _ctx: ctx,
+            surface,
+            current_size: PhySize::new(256, 256),
+            damaged: true,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl WindowHandler for AppWindowHandler {
+    fn on_frame(&mut self, _window: &mut Window) {
+        let mut buf = self.surface.buffer_mut().unwrap();
+        if self.damaged {
+            buf.fill(self.color);
+            self.damaged = false;
+        }
+        buf.present().unwrap();
+    }
+
+    fn on_event(&mut self, window: &mut Window, event: Event) -> EventStatus {
+        match event {
+            Event::Window(WindowEvent::Resized(info)) => {
+                let new_size = info.physical_size();
+                self.current_size = new_size;
+
+                if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(new_size.width), NonZeroU32::new(new_size.height))
+                {
+                    self.surface.resize(width, height).unwrap();
+                    self.damaged = true;
+                }
+            }
+            Event::Mouse(MouseEvent::ButtonPressed { .. }) => {
+                let name = format!("{} > child", self.name);
+                let color = self.color.rotate_left(8) | 0xFF000000;
+                let result = window.try_open_window(window_open_options(&name), move |window| {
+                    AppWindowHandler::new(window, name, color)
+                });
+                if let Err(err) = result {
+                    eprintln!("Could not open a window: {}", err);
+                }
+            }
+            Event::Window(WindowEvent::WillClose) => println!("{} closed", self.name),
+            _ => {}
+        }
+
+        EventStatus::Captured
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn window_open_options(title: &str) -> WindowOpenOptions {
+    WindowOpenOptions {
+        title: title.into(),
+        size: Size::new(256.0, 256.0),
+        scale: WindowScalePolicy::SystemScaleFactor,
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn main() {
+    let mut application = Application::new();
+
+    for (name, color) in [("mixer", 0xFFAA0000u32), ("browser", 0xFF00AA00)].iter().copied() {
+        application.open_window(window_open_options(name), move |window| {
+            AppWindowHandler::new(window, name.to_owned(), color)
+        });
+    }
+
+    application.run();
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("Application is only available on Linux for now");
+}
@@ -23,10 +23,7 @@ impl ParentWindowHandler {
             title: "baseview child".into(),
             size: baseview::Size::new(256.0, 256.0),
             scale: WindowScalePolicy::SystemScaleFactor,
-
-            // TODO: Add an example that uses the OpenGL context
-            #[cfg(feature = "opengl")]
-            gl_config: None,
+            ..Default::default()
         };
         let child_window =
             Window::open_parented(window, window_open_options, ChildWindowHandler::new);
@@ -69,6 +66,8 @@ impl WindowHandler for ParentWindowHandler {
             Event::Mouse(e) => println!("Parent Mouse event: {:?}", e),
             Event::Keyboard(e) => println!("Parent Keyboard event: {:?}", e),
             Event::Window(e) => println!("Parent Window event: {:?}", e),
+            Event::Clipboard(e) => println!("Parent Clipboard event: {:?}", e),
+            Event::User(e) => println!("Parent User event: {:?}", e),
         }
 
         EventStatus::Captured
@@ -120,6 +119,8 @@ impl WindowHandler for ChildWindowHandler {
             Event::Mouse(e) => println!("Child Mouse event: {:?}", e),
             Event::Keyboard(e) => println!("Child Keyboard event: {:?}", e),
             Event::Window(e) => println!("Child Window event: {:?}", e),
+            Event::Clipboard(e) => println!("Child Clipboard event: {:?}", e),
+            Event::User(e) => println!("Child User event: {:?}", e),
         }
 
         EventStatus::Captured
@@ -131,10 +132,7 @@ fn main() {
         title: "baseview".into(),
         size: baseview::Size::new(512.0, 512.0),
         scale: WindowScalePolicy::SystemScaleFactor,
-
-        // TODO: Add an example that uses the OpenGL context
-        #[cfg(feature = "opengl")]
-        gl_config: None,
+        ..Default::default()
     };
 
     Window::open_blocking(window_open_options, ParentWindowHandler::new);
@@ -1,9 +1,10 @@
 use std::num::NonZeroU32;
 use std::time::Duration;
 
+#[cfg(not(target_os = "linux"))]
 use rtrb::{Consumer, RingBuffer};
 
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "linux"))]
 use baseview::{copy_to_clipboard, MouseEvent};
 use baseview::{
     Event, EventStatus, PhySize, Window, WindowEvent, WindowHandler, WindowScalePolicy,
@@ -15,6 +16,9 @@ enum Message {
 }
 
 struct OpenWindowExample {
+    // The event proxy only delivers messages on Linux for now, so the other platforms still poll
+    // a ring buffer for them
+    #[cfg(not(target_os = "linux"))]
     rx: Consumer<Message>,
 
     _ctx: softbuffer::Context,
@@ -32,6 +36,7 @@ impl WindowHandler for OpenWindowExample {
         }
         buf.present().unwrap();
 
+        #[cfg(not(target_os = "linux"))]
         while let Ok(message) = self.rx.pop() {
             println!("Message: {:?}", message);
         }
@@ -39,7 +44,7 @@ impl WindowHandler for OpenWindowExample {
 
     fn on_event(&mut self, _window: &mut Window, event: Event) -> EventStatus {
         match &event {
-            #[cfg(target_os = "macos")]
+            #[cfg(any(target_os = "macos", target_os = "linux"))]
             Event::Mouse(MouseEvent::ButtonPressed { .. }) => copy_to_clipboard("This is a test!"),
             Event::Window(WindowEvent::Resized(info)) => {
                 println!("Resized: {:?}", info);
@@ -53,6 +58,11 @@ impl WindowHandler for OpenWindowExample {
                     self.damaged = true;
                 }
             }
+            Event::User(event) => {
+                if let Some(message) = event.downcast_ref::<Message>() {
+                    println!("Message: {:?}", message);
+                }
+            }
             _ => {}
         }
 
@@ -67,14 +77,16 @@ fn main() {
         title: "baseview".into(),
         size: baseview::Size::new(512.0, 512.0),
         scale: WindowScalePolicy::SystemScaleFactor,
-
-        // TODO: Add an example that uses the OpenGL context
-        #[cfg(feature = "opengl")]
-        gl_config: None,
+        icon: Some(example_icon()),
+        position: baseview::WindowPosition::CenteredOnScreen,
+        min_size: Some(baseview::Size::new(256.0, 256.0)),
+        ..Default::default()
     };
 
+    #[cfg(not(target_os = "linux"))]
     let (mut tx, rx) = RingBuffer::new(128);
 
+    #[cfg(not(target_os = "linux"))]
     std::thread::spawn(move || loop {
         std::thread::sleep(Duration::from_secs(5));
 
@@ -84,14 +96,29 @@ fn main() {
     });
 
     Window::open_blocking(window_open_options, |window| {
+        #[cfg(target_os = "linux")]
+        {
+            let event_proxy = window.event_proxy();
+
+            std::thread::spawn(move || loop {
+                std::thread::sleep(Duration::from_secs(5));
+
+                if !event_proxy.send_event(Message::Hello) {
+                    println!("Failed sending message");
+                    break;
+                }
+            });
+        }
+
         let ctx = unsafe { softbuffer::Context::new(window) }.unwrap();
         let mut surface = unsafe { softbuffer::Surface::new(&ctx, window) }.unwrap();
         surface.resize(NonZeroU32::new(512).unwrap(), NonZeroU32::new(512).unwrap()).unwrap();
 
         OpenWindowExample {
+            #[cfg(not(target_os = "linux"))]
+            rx,
             _ctx: ctx,
             surface,
-            rx,
             current_size: PhySize::new(512, 512),
             damaged: true,
         }
@@ -103,5 +130,25 @@ fn log_event(event: &Event) {
         Event::Mouse(e) => println!("Mouse event: {:?}", e),
         Event::Keyboard(e) => println!("Keyboard event: {:?}", e),
         Event::Window(e) => println!("Window event: {:?}", e),
+        Event::Clipboard(e) => println!("Clipboard event: {:?}", e),
+        Event::User(e) => println!("User event: {:?}", e),
+    }
+}
+
+/// A filled circle with a soft edge, to show up in the taskbar.
+fn example_icon() -> baseview::Icon {
+    const SIZE: u32 = 64;
+
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (dx, dy) = (x as f32 + 0.5 - SIZE as f32 / 2.0, y as f32 + 0.5 - SIZE as f32 / 2.0);
+            let distance = (dx * dx + dy * dy).sqrt();
+            let alpha = (SIZE as f32 / 2.0 - distance).clamp(0.0, 1.0);
+
+            rgba.extend_from_slice(&[0x40, 0x90, 0xE0, (alpha * 255.0) as u8]);
+        }
     }
+
+    baseview::Icon::from_rgba(SIZE, SIZE, rgba)
 }
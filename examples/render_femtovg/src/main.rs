@@ -1,7 +1,7 @@
 use baseview::gl::GlConfig;
 use baseview::{
-    Event, EventStatus, MouseEvent, PhyPoint, Size, Window, WindowEvent, WindowHandler, WindowInfo,
-    WindowOpenOptions, WindowScalePolicy,
+    Event, EventStatus, FrameClock, MouseEvent, PhyPoint, RedrawPolicy, Size, Window, WindowEvent,
+    WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy,
 };
 use femtovg::renderer::OpenGl;
 use femtovg::{Canvas, Color};
@@ -75,7 +75,7 @@ impl WindowHandler for FemtovgExample {
         self.damaged = false;
     }
 
-    fn on_event(&mut self, _window: &mut Window, event: Event) -> EventStatus {
+    fn on_event(&mut self, window: &mut Window, event: Event) -> EventStatus {
         match event {
             Event::Window(WindowEvent::Resized(size)) => {
                 let phy_size = size.physical_size();
@@ -89,6 +89,9 @@ impl WindowHandler for FemtovgExample {
             }
             _ => {}
         };
+        if self.damaged {
+            window.request_redraw();
+        }
         log_event(&event);
         EventStatus::Captured
     }
@@ -99,8 +102,11 @@ fn main() {
         title: "Femtovg on Baseview".into(),
         size: Size::new(512.0, 512.0),
         scale: WindowScalePolicy::SystemScaleFactor,
+        frame_clock: FrameClock::Vsync,
+        redraw_policy: RedrawPolicy::OnDemand,
 
         gl_config: Some(GlConfig { alpha_bits: 8, ..GlConfig::default() }),
+        ..Default::default()
     };
 
     Window::open_blocking(window_open_options, FemtovgExample::new);
@@ -111,5 +117,7 @@ fn log_event(event: &Event) {
         Event::Mouse(e) => println!("Mouse event: {:?}", e),
         Event::Keyboard(e) => println!("Keyboard event: {:?}", e),
         Event::Window(e) => println!("Window event: {:?}", e),
+        Event::Clipboard(e) => println!("Clipboard event: {:?}", e),
+        Event::User(e) => println!("User event: {:?}", e),
     }
 }
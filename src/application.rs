@@ -0,0 +1,58 @@
+use std::marker::PhantomData;
+
+use crate::x11 as platform;
+use crate::{Error, Window, WindowHandle, WindowHandler, WindowOpenOptions};
+
+/// The entry point for standalone applications that need more than one top-level window. Open the
+/// application's initial windows with [`open_window()`][Self::open_window()], and then call
+/// [`run()`][Self::run()] to wait until all of them have been closed. The windows' handlers can
+/// open more windows that belong to the application using
+/// [Window::open_window](`crate::Window::open_window()`).
+///
+/// This is only available on Linux for now.
+pub struct Application {
+    application: platform::Application,
+    // so that Application is !Send on all platforms
+    phantom: PhantomData<*mut ()>,
+}
+
+impl Application {
+    pub fn new() -> Self {
+        Self { application: platform::Application::new(), phantom: PhantomData }
+    }
+
+    /// Open a top-level window that belongs to the application.
+    pub fn open_window<H, B>(&mut self, options: WindowOpenOptions, build: B) -> WindowHandle
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        WindowHandle::new(self.application.open_window::<H, B>(options, build))
+    }
+
+    /// The same as [`open_window()`][Self::open_window()], but returns an error instead of
+    /// panicking when the window could not be opened.
+    pub fn try_open_window<H, B>(
+        &mut self, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        self.application.try_open_window::<H, B>(options, build).map(WindowHandle::new)
+    }
+
+    /// Block until all of the application's windows have closed, including the ones that were
+    /// opened by the windows' handlers.
+    pub fn run(self) {
+        self.application.run();
+    }
+}
+
+impl Default for Application {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,6 +5,70 @@ use crate::win as platform;
 #[cfg(target_os = "linux")]
 use crate::x11 as platform;
 
+/// Copy `data` to the system clipboard.
+///
+/// On Linux the clipboard is owned by one of baseview's windows, so this should be called from
+/// within a [`WindowHandler`][crate::WindowHandler] callback.
 pub fn copy_to_clipboard(data: &str) {
     platform::copy_to_clipboard(data)
 }
+
+/// The same as [`copy_to_clipboard()`], but for any kind of [`ClipboardData`]. Applications
+/// pasting the data can choose whichever representation suits them best, so the same data can be
+/// copied in multiple formats at once (e.g. a serialized preset as a custom MIME type along with a
+/// plain text version of it).
+///
+/// On Windows and macOS only the text is copied for now. Nothing is copied if `data` doesn't
+/// contain any text.
+pub fn copy_data_to_clipboard(data: Vec<ClipboardData>) {
+    platform::copy_data_to_clipboard(data)
+}
+
+/// Set the `PRIMARY` selection to `data`. On Linux this is what gets pasted when middle clicking,
+/// and applications usually set it whenever the user selects some text. Other platforms don't have
+/// a primary selection, so this does nothing there.
+///
+/// Like [`copy_to_clipboard()`], this should be called from within a
+/// [`WindowHandler`][crate::WindowHandler] callback.
+pub fn copy_to_primary_selection(data: &str) {
+    platform::copy_to_primary_selection(data)
+}
+
+/// Data that can be copied to and read from the clipboard.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardData {
+    /// Plain text.
+    Text(String),
+    /// A list of URIs, such as `file://` URIs for files copied in a file manager.
+    UriList(Vec<String>),
+    /// A PNG encoded image.
+    Png(Vec<u8>),
+    /// Arbitrary data identified by a MIME type.
+    Custom { mime: String, data: Vec<u8> },
+}
+
+impl ClipboardData {
+    /// The format this data would be requested as.
+    pub fn format(&self) -> ClipboardFormat {
+        match self {
+            ClipboardData::Text(_) => ClipboardFormat::Text,
+            ClipboardData::UriList(_) => ClipboardFormat::UriList,
+            ClipboardData::Png(_) => ClipboardFormat::Png,
+            ClipboardData::Custom { mime, .. } => ClipboardFormat::Custom(mime.clone()),
+        }
+    }
+}
+
+/// The formats clipboard data can be requested in, see
+/// [Window::request_clipboard_data](`crate::Window::request_clipboard_data()`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClipboardFormat {
+    /// Plain text, see [`ClipboardData::Text`].
+    Text,
+    /// A `text/uri-list`, see [`ClipboardData::UriList`].
+    UriList,
+    /// An `image/png`, see [`ClipboardData::Png`].
+    Png,
+    /// Data with the given MIME type, see [`ClipboardData::Custom`].
+    Custom(String),
+}
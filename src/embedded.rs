@@ -0,0 +1,194 @@
+use std::os::fd::RawFd;
+use std::time::Instant;
+
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+use crate::x11;
+use crate::WindowHandle;
+
+/// A child window whose event loop is driven by the host instead of running on a thread of its
+/// own. Returned by [`Window::open_parented_embedded()`][crate::Window::open_parented_embedded()].
+///
+/// The host should call [`pump_events()`][Self::pump_events()] whenever [`fd()`][Self::fd()]
+/// becomes readable, and [`on_timer()`][Self::on_timer()] once the
+/// [`timer_deadline()`][Self::timer_deadline()] has passed. Hosts that only offer a fixed rate
+/// timer can instead call `on_timer()` at the window's frame rate, as it doesn't do anything when
+/// nothing is due. All of the window handler's callbacks are made from these two functions.
+///
+/// The window closes when this handle is dropped. If the window owns the clipboard at that point,
+/// dropping the handle blocks the host's thread for up to half a second while a clipboard manager
+/// copies the clipboard's contents.
+pub struct EmbeddedWindowHandle {
+    window_handle: WindowHandle,
+    embedded_window: x11::EmbeddedWindow,
+}
+
+impl EmbeddedWindowHandle {
+    pub(crate) fn new(window_handle: WindowHandle, embedded_window: x11::EmbeddedWindow) -> Self {
+        Self { window_handle, embedded_window }
+    }
+
+    /// The regular handle to the window, for closing it and for sending it events and tasks.
+    /// Tasks sent with [`WindowHandle::run_on_window_thread()`] run during the next call to
+    /// [`pump_events()`][Self::pump_events()].
+    pub fn window_handle(&self) -> &WindowHandle {
+        &self.window_handle
+    }
+
+    pub fn window_handle_mut(&mut self) -> &mut WindowHandle {
+        &mut self.window_handle
+    }
+
+    /// The file descriptor the host should watch for readability. This stays the same for as long
+    /// as the window is open.
+    pub fn fd(&self) -> RawFd {
+        self.embedded_window.fd()
+    }
+
+    /// When [`on_timer()`][Self::on_timer()] should be called next, if the window is waiting for
+    /// anything. This changes after every call to `pump_events()` and `on_timer()`, and after the
+    /// window handler has requested a redraw.
+    pub fn timer_deadline(&self) -> Option<Instant> {
+        self.embedded_window.timer_deadline()
+    }
+
+    /// Handle all pending events. This never blocks.
+    pub fn pump_events(&mut self) {
+        self.embedded_window.pump_events();
+    }
+
+    /// Draw the next frame if it's due, and time out any unresponsive clipboard and drag and drop
+    /// transfers. This never blocks.
+    pub fn on_timer(&mut self) {
+        self.embedded_window.on_timer();
+    }
+}
+
+unsafe impl HasRawWindowHandle for EmbeddedWindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.window_handle.raw_window_handle()
+    }
+}
+
+#[cfg(feature = "calloop")]
+pub use self::calloop_source::EmbeddedWindowSource;
+
+#[cfg(feature = "calloop")]
+mod calloop_source {
+    use std::os::fd::RawFd;
+
+    use calloop::generic::{FdWrapper, Generic};
+    use calloop::timer::{TimeoutAction, Timer};
+    use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+
+    use super::EmbeddedWindowHandle;
+
+    /// A [`calloop`] event source that drives an [`EmbeddedWindowHandle`]. The source's callback
+    /// is called with the window's handle after every round of events, and the source removes
+    /// itself once the window has closed.
+    ///
+    /// This is only available with the `calloop` feature.
+    pub struct EmbeddedWindowSource {
+        fd: Generic<FdWrapper<RawFd>>,
+        /// Set while the window is waiting for a deadline.
+        timer: Option<Timer>,
+        window: EmbeddedWindowHandle,
+    }
+
+    impl EmbeddedWindowSource {
+        pub fn new(window: EmbeddedWindowHandle) -> Self {
+            // SAFETY: the file descriptor stays valid until the window handle is dropped, and the
+            //         handle is dropped after `fd` as it's declared after it
+            let fd = unsafe { FdWrapper::new(window.fd()) };
+
+            Self { fd: Generic::new(fd, Interest::READ, Mode::Level), timer: None, window }
+        }
+
+        pub fn window(&self) -> &EmbeddedWindowHandle {
+            &self.window
+        }
+
+        pub fn window_mut(&mut self) -> &mut EmbeddedWindowHandle {
+            &mut self.window
+        }
+
+        fn register_timer(
+            &mut self, poll: &mut Poll, token_factory: &mut TokenFactory,
+        ) -> calloop::Result<()> {
+            self.timer = self.window.timer_deadline().map(Timer::from_deadline);
+            if let Some(timer) = &mut self.timer {
+                timer.register(poll, token_factory)?;
+            }
+
+            Ok(())
+        }
+
+        fn unregister_timer(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+            if let Some(mut timer) = self.timer.take() {
+                timer.unregister(poll)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl EventSource for EmbeddedWindowSource {
+        type Event = ();
+        type Metadata = EmbeddedWindowHandle;
+        type Ret = ();
+        type Error = std::io::Error;
+
+        fn process_events<F>(
+            &mut self, readiness: Readiness, token: Token, mut callback: F,
+        ) -> Result<PostAction, Self::Error>
+        where
+            F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+        {
+            let window = &mut self.window;
+            self.fd.process_events(readiness, token, |_, _| {
+                window.pump_events();
+                Ok(PostAction::Continue)
+            })?;
+            if let Some(timer) = &mut self.timer {
+                // The timer gets registered again with the window's new deadline below
+                timer.process_events(readiness, token, |_, _| {
+                    window.on_timer();
+                    TimeoutAction::Drop
+                })?;
+            }
+
+            callback((), &mut self.window);
+
+            if !self.window.window_handle().is_open() {
+                return Ok(PostAction::Remove);
+            }
+
+            // Handling the events will usually have moved the window's deadline
+            if self.timer.is_some() || self.window.timer_deadline().is_some() {
+                Ok(PostAction::Reregister)
+            } else {
+                Ok(PostAction::Continue)
+            }
+        }
+
+        fn register(
+            &mut self, poll: &mut Poll, token_factory: &mut TokenFactory,
+        ) -> calloop::Result<()> {
+            self.fd.register(poll, token_factory)?;
+            self.register_timer(poll, token_factory)
+        }
+
+        fn reregister(
+            &mut self, poll: &mut Poll, token_factory: &mut TokenFactory,
+        ) -> calloop::Result<()> {
+            self.fd.reregister(poll, token_factory)?;
+            self.unregister_timer(poll)?;
+            self.register_timer(poll, token_factory)
+        }
+
+        fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+            self.fd.unregister(poll)?;
+            self.unregister_timer(poll)
+        }
+    }
+}
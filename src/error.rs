@@ -0,0 +1,41 @@
+use std::fmt::{self, Display, Formatter};
+
+/// The errors that can occur while opening a window.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Could not connect to the windowing system. On Linux this happens when the `DISPLAY`
+    /// environment variable isn't set, or when the X server can't be reached.
+    ConnectionFailed(String),
+    /// The parent window handle is of a type that isn't supported on this platform, or it doesn't
+    /// refer to an existing window.
+    InvalidParent,
+    /// Could not find a visual or pixel format the window can use.
+    VisualNegotiationFailed(String),
+    /// Could not create the window's OpenGL context.
+    #[cfg(feature = "opengl")]
+    GlCreationFailed(crate::gl::GlError),
+    /// The windowing system reported an error while creating the window.
+    WindowCreationFailed(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConnectionFailed(err) => {
+                write!(f, "Could not connect to the windowing system: {}", err)
+            }
+            Error::InvalidParent => write!(f, "The parent window handle is not valid"),
+            Error::VisualNegotiationFailed(err) => {
+                write!(f, "Could not find a suitable visual: {}", err)
+            }
+            #[cfg(feature = "opengl")]
+            Error::GlCreationFailed(err) => {
+                write!(f, "Could not create the OpenGL context: {:?}", err)
+            }
+            Error::WindowCreationFailed(err) => write!(f, "Could not create the window: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,8 +1,11 @@
+use std::any::Any;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use keyboard_types::{KeyboardEvent, Modifiers};
 
-use crate::{Point, WindowInfo};
+use crate::{ClipboardData, ClipboardFormat, PhyRect, Point, WindowInfo};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum MouseButton {
@@ -106,21 +109,100 @@ pub enum MouseEvent {
         /// Data being dragged
         data: DropData,
     },
+
+    /// A drag started with [Window::start_drag](`crate::Window::start_drag()`) has ended.
+    DragFinished {
+        /// What the drop target did with the data, or `None` if the drag was cancelled or the
+        /// drop target rejected the data.
+        effect: Option<DropEffect>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum WindowEvent {
     Resized(WindowInfo),
+    /// The window has moved. Contains the new logical position of the top left corner of the
+    /// window's frame, as returned by [`Window::position()`][crate::Window::position()].
+    ///
+    /// This is currently only sent on Linux.
+    Moved(Point),
+    /// These parts of the window have been uncovered or otherwise lost their contents, and need to
+    /// be drawn again. All damage reported since the last frame is sent at once, right before
+    /// [`on_frame()`][crate::WindowHandler::on_frame()] gets called.
+    ///
+    /// This is currently only sent on Linux.
+    Damaged(Vec<PhyRect>),
     Focused,
     Unfocused,
+    /// The user asked to close the window, for instance by clicking its close button. Return
+    /// [`EventStatus::Captured`] to keep the window open, for example to ask whether unsaved
+    /// changes should be saved first. The window can then still be closed later using
+    /// [Window::close](`crate::Window::close()`). Returning [`EventStatus::Ignored`] closes the
+    /// window.
+    CloseRequested,
+    /// The window is about to close.
     WillClose,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardEvent {
+    /// The clipboard's contents as requested through
+    /// [Window::request_clipboard_text](`crate::Window::request_clipboard_text()`), or `None` if
+    /// the clipboard is empty or doesn't contain any text.
+    Text(Option<String>),
+    /// The clipboard's contents as requested through
+    /// [Window::request_clipboard_data](`crate::Window::request_clipboard_data()`), or `None` if
+    /// the clipboard is empty or can't provide the requested format.
+    Data(Option<ClipboardData>),
+    /// The formats the clipboard's contents are available in, as requested through
+    /// [Window::request_clipboard_formats](`crate::Window::request_clipboard_formats()`).
+    Formats(Vec<ClipboardFormat>),
+    /// The `PRIMARY` selection's contents as requested through
+    /// [Window::request_primary_selection_text](`crate::Window::request_primary_selection_text()`),
+    /// or `None` if nothing is selected or the selection doesn't contain any text.
+    PrimaryText(Option<String>),
+    /// The clipboard's contents have changed, either because another application copied something
+    /// or because the application that owned the clipboard's contents has closed.
+    ///
+    /// Currently only sent on Linux, and only if the X server supports the XFixes extension.
+    Changed,
+}
+
 #[derive(Debug, Clone)]
 pub enum Event {
     Mouse(MouseEvent),
     Keyboard(KeyboardEvent),
     Window(WindowEvent),
+    Clipboard(ClipboardEvent),
+    /// A message sent through an [`EventProxy`][crate::EventProxy].
+    User(UserEvent),
+}
+
+/// A message of any type sent to a window from another thread using
+/// [EventProxy::send_event](`crate::EventProxy::send_event()`).
+#[derive(Clone)]
+pub struct UserEvent(Arc<dyn Any + Send + Sync>);
+
+impl UserEvent {
+    pub(crate) fn new<T: Any + Send + Sync>(payload: T) -> Self {
+        Self(Arc::new(payload))
+    }
+
+    /// Returns `true` if the message is of type `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.0.is::<T>()
+    }
+
+    /// Returns a reference to the message if it is of type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl fmt::Debug for UserEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserEvent").finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -135,6 +217,28 @@ pub enum DropEffect {
 pub enum DropData {
     None,
     Files(Vec<PathBuf>),
+    /// Plain text.
+    Text(String),
+    /// A list of URLs that don't all point to local files.
+    Urls(Vec<String>),
+    /// Data in some other format, like `audio/midi`.
+    Mime {
+        /// The data's MIME type.
+        mime: String,
+        /// The payload. Since this may be large, it's only transferred once the data has been
+        /// dropped, so this is always `None` for [`MouseEvent::DragEntered`] and
+        /// [`MouseEvent::DragMoved`]. It's also `None` if the drag source failed to send it.
+        data: Option<Vec<u8>>,
+    },
+}
+
+/// Data that can be dragged out of a window using
+/// [Window::start_drag](`crate::Window::start_drag()`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DragData {
+    /// A list of absolute file paths.
+    Files(Vec<PathBuf>),
+    Text(String),
 }
 
 /// Return value for [WindowHandler::on_event](`crate::WindowHandler::on_event()`),
@@ -144,7 +248,7 @@ pub enum DropData {
 /// For most event types, this value won't have any effect. This is the case
 /// when there is no clear meaning of passing back the event to the platform,
 /// or it isn't obviously useful. Currently, only [`Event::Keyboard`] variants
-/// are supported.
+/// and [`WindowEvent::CloseRequested`] are supported.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EventStatus {
     /// Event was handled by your window and will not be sent back to the
@@ -0,0 +1,38 @@
+/// An image used as a window's icon, for instance in the taskbar and in the window's title bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Icon {
+    /// Create an icon from 8-bit RGBA pixels that are not premultiplied, stored row by row starting
+    /// at the top left corner. Pass the largest version of the icon you have, as it's scaled down
+    /// to the sizes the system asks for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rgba` doesn't contain exactly `width * height * 4` bytes.
+    pub fn from_rgba(width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        assert_eq!(
+            rgba.len(),
+            width as usize * height as usize * 4,
+            "The icon's RGBA buffer doesn't match its size"
+        );
+
+        Self { width, height, rgba }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+}
@@ -1,3 +1,12 @@
+//! Cross-platform windowing for audio plugin editors and standalone applications.
+//!
+//! # Features
+//!
+//! - `opengl`: creates an OpenGL context for windows that ask for one through
+//!   `WindowOpenOptions::gl_config`.
+//! - `calloop`: adds `EmbeddedWindowSource` on Linux, a [calloop](https://docs.rs/calloop) event
+//!   source for driving the windows opened with `Window::open_parented_embedded()`.
+
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "windows")]
@@ -5,8 +14,14 @@ mod win;
 #[cfg(target_os = "linux")]
 mod x11;
 
+#[cfg(target_os = "linux")]
+mod application;
 mod clipboard;
+#[cfg(target_os = "linux")]
+mod embedded;
+mod error;
 mod event;
+mod icon;
 mod keyboard;
 mod mouse_cursor;
 mod window;
@@ -16,8 +31,14 @@ mod window_open_options;
 #[cfg(feature = "opengl")]
 pub mod gl;
 
+#[cfg(target_os = "linux")]
+pub use application::Application;
 pub use clipboard::*;
+#[cfg(target_os = "linux")]
+pub use embedded::*;
+pub use error::Error;
 pub use event::*;
+pub use icon::Icon;
 pub use mouse_cursor::MouseCursor;
 pub use window::*;
 pub use window_info::*;
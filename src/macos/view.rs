@@ -267,6 +267,11 @@ extern "C" fn resign_first_responder(this: &Object, _sel: Sel) -> BOOL {
 extern "C" fn window_should_close(this: &Object, _: Sel, _sender: id) -> BOOL {
     let state = unsafe { WindowState::from_view(this) };
 
+    // The handler may want to keep the window open
+    if state.trigger_event(Event::Window(WindowEvent::CloseRequested)) == EventStatus::Captured {
+        return NO;
+    }
+
     state.trigger_event(Event::Window(WindowEvent::WillClose));
 
     state.window_inner.close();
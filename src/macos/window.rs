@@ -3,6 +3,7 @@ use std::collections::VecDeque;
 use std::ffi::c_void;
 use std::ptr;
 use std::rc::Rc;
+use std::time::Duration;
 
 use cocoa::appkit::{
     NSApp, NSApplication, NSApplicationActivationPolicyRegular, NSBackingStoreBuffered,
@@ -22,8 +23,9 @@ use raw_window_handle::{
 };
 
 use crate::{
-    Event, EventStatus, MouseCursor, Size, WindowHandler, WindowInfo, WindowOpenOptions,
-    WindowScalePolicy,
+    ClipboardData, ClipboardFormat, DragData, DropEffect, Error, Event, EventStatus, Icon,
+    MouseCursor, Point, Size, UserEvent, WindowHandler, WindowInfo, WindowOpenOptions,
+    WindowScalePolicy, WindowTask,
 };
 
 use super::keyboard::KeyboardState;
@@ -44,6 +46,29 @@ impl WindowHandle {
     pub fn is_open(&self) -> bool {
         self.state.window_inner.open.get()
     }
+
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy {}
+    }
+
+    pub fn run_on_window_thread(&self, _task: WindowTask) -> bool {
+        // TODO: Queue the task and run it from the window's event loop
+        false
+    }
+
+    pub fn is_on_window_thread(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone)]
+pub struct EventProxy {}
+
+impl EventProxy {
+    pub fn send_event(&self, _event: UserEvent) -> bool {
+        // TODO: Queue the event and wake up the main run loop to deliver it
+        false
+    }
 }
 
 unsafe impl HasRawWindowHandle for WindowHandle {
@@ -63,6 +88,7 @@ pub(super) struct WindowInner {
     ns_window: Cell<Option<id>>,
     /// Our subclassed NSView
     ns_view: id,
+    frame_interval: Cell<Duration>,
 
     #[cfg(feature = "opengl")]
     gl_context: Option<GlContext>,
@@ -156,6 +182,7 @@ impl<'a> Window<'a> {
             ns_app: Cell::new(None),
             ns_window: Cell::new(None),
             ns_view,
+            frame_interval: Cell::new(options.frame_interval()),
 
             #[cfg(feature = "opengl")]
             gl_context: options
@@ -174,6 +201,34 @@ impl<'a> Window<'a> {
         window_handle
     }
 
+    pub fn try_open_parented<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        if !matches!(parent.raw_window_handle(), RawWindowHandle::AppKit(_)) {
+            return Err(Error::InvalidParent);
+        }
+
+        // TODO: Return an error instead of panicking when creating the view fails
+        Ok(Self::open_parented::<P, H, B>(parent, options, build))
+    }
+
+    pub fn try_open_blocking<H, B>(options: WindowOpenOptions, build: B) -> Result<(), Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        // TODO: Return an error instead of panicking when creating the window fails
+        Self::open_blocking::<H, B>(options, build);
+        Ok(())
+    }
+
     pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
     where
         H: WindowHandler + 'static,
@@ -231,6 +286,7 @@ impl<'a> Window<'a> {
             ns_app: Cell::new(Some(app)),
             ns_window: Cell::new(Some(ns_window)),
             ns_view,
+            frame_interval: Cell::new(options.frame_interval()),
 
             #[cfg(feature = "opengl")]
             gl_context: options
@@ -285,6 +341,70 @@ impl<'a> Window<'a> {
         self.inner.close();
     }
 
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy {}
+    }
+
+    pub fn request_redraw(&mut self) {
+        // TODO: Support `RedrawPolicy::OnDemand`, for now we're always drawing continuously
+    }
+
+    pub fn set_title(&mut self, _title: &str) {
+        // TODO: Set the `NSWindow`'s title when the window isn't parented
+    }
+
+    pub fn set_icon(&mut self, _icon: &Icon) {
+        // TODO: macOS shows the application's icon instead of a per-window icon. Standalone
+        //       applications could set `NSApplication`'s `applicationIconImage`.
+    }
+
+    pub fn position(&self) -> Point {
+        // TODO: Track the window's position
+        Point::new(0.0, 0.0)
+    }
+
+    pub fn set_position(&mut self, _position: Point) {
+        // TODO: Move the window
+    }
+
+    pub fn set_resizable(&mut self, _resizable: bool) {
+        // TODO: Update the window's style mask
+    }
+
+    pub fn set_min_size(&mut self, _min_size: Option<Size>) {
+        // TODO: Set the window's `contentMinSize`
+    }
+
+    pub fn set_max_size(&mut self, _max_size: Option<Size>) {
+        // TODO: Set the window's `contentMaxSize`
+    }
+
+    pub fn set_aspect_ratio(&mut self, _aspect_ratio: Option<f64>) {
+        // TODO: Set the window's `contentAspectRatio`
+    }
+
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.inner.frame_interval.set(interval);
+
+        // A timer's interval can't be changed, so it needs to be replaced. The window state isn't
+        // attached to the view yet while the handler is being built, in which case the timer will
+        // be set up with the new interval later.
+        if self.inner.open.get() {
+            unsafe {
+                let state_ptr: *const c_void = *(*self.inner.ns_view).get_ivar(BASEVIEW_STATE_IVAR);
+                if state_ptr.is_null() {
+                    return;
+                }
+
+                let window_state = state_ptr as *const WindowState;
+                if let Some(frame_timer) = (*window_state).frame_timer.take() {
+                    CFRunLoop::get_current().remove_timer(&frame_timer, kCFRunLoopDefaultMode);
+                }
+                WindowState::setup_timer(window_state);
+            }
+        }
+    }
+
     pub fn has_focus(&mut self) -> bool {
         unsafe {
             let view = self.inner.ns_view.as_mut().unwrap();
@@ -309,6 +429,26 @@ impl<'a> Window<'a> {
         }
     }
 
+    pub fn request_clipboard_text(&mut self) {
+        // TODO: Read `NSPasteboardTypeString` from the general pasteboard
+    }
+
+    pub fn request_clipboard_data(&mut self, _format: ClipboardFormat) {
+        // TODO: Map the formats to pasteboard types
+    }
+
+    pub fn request_clipboard_formats(&mut self) {
+        // TODO: Map the pasteboard's types to formats
+    }
+
+    pub fn request_primary_selection_text(&mut self) {
+        // There's no primary selection on macOS
+    }
+
+    pub fn start_drag(&mut self, _data: DragData, _allowed_effects: &[DropEffect]) {
+        // TODO: Start a dragging session on the view
+    }
+
     pub fn resize(&mut self, size: Size) {
         if self.inner.open.get() {
             // NOTE: macOS gives you a personal rave if you pass in fractional pixels here. Even
@@ -437,7 +577,8 @@ impl WindowState {
             copyDescription: None,
         };
 
-        let timer = CFRunLoopTimer::new(0.0, 0.015, 0, 0, timer_callback, &mut timer_context);
+        let interval = (*window_state_ptr).window_inner.frame_interval.get().as_secs_f64();
+        let timer = CFRunLoopTimer::new(0.0, interval, 0, 0, timer_callback, &mut timer_context);
 
         CFRunLoop::get_current().add_timer(&timer, kCFRunLoopDefaultMode);
 
@@ -479,3 +620,17 @@ pub fn copy_to_clipboard(string: &str) {
         pb.setString_forType(ns_str, cocoa::appkit::NSPasteboardTypeString);
     }
 }
+
+pub fn copy_data_to_clipboard(data: Vec<ClipboardData>) {
+    // TODO: Write the other formats to the pasteboard as well
+    for data in data {
+        if let ClipboardData::Text(text) = data {
+            copy_to_clipboard(&text);
+            return;
+        }
+    }
+}
+
+pub fn copy_to_primary_selection(_data: &str) {
+    // There's no primary selection on macOS
+}
@@ -4,19 +4,21 @@ use winapi::shared::windef::{HWND, RECT};
 use winapi::um::combaseapi::CoCreateGuid;
 use winapi::um::ole2::{OleInitialize, RegisterDragDrop, RevokeDragDrop};
 use winapi::um::oleidl::LPDROPTARGET;
+use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
 use winapi::um::winuser::{
-    AdjustWindowRectEx, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW,
-    GetDpiForWindow, GetFocus, GetMessageW, GetWindowLongPtrW, LoadCursorW, PostMessageW,
-    RegisterClassW, ReleaseCapture, SetCapture, SetCursor, SetFocus, SetProcessDpiAwarenessContext,
-    SetTimer, SetWindowLongPtrW, SetWindowPos, TrackMouseEvent, TranslateMessage, UnregisterClassW,
-    CS_OWNDC, GET_XBUTTON_WPARAM, GWLP_USERDATA, HTCLIENT, IDC_ARROW, MSG, SWP_NOMOVE,
-    SWP_NOZORDER, TRACKMOUSEEVENT, WHEEL_DELTA, WM_CHAR, WM_CLOSE, WM_CREATE, WM_DPICHANGED,
-    WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
-    WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSELEAVE, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_NCDESTROY,
-    WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETCURSOR, WM_SHOWWINDOW, WM_SIZE, WM_SYSCHAR, WM_SYSKEYDOWN,
-    WM_SYSKEYUP, WM_TIMER, WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW, WS_CAPTION, WS_CHILD,
-    WS_CLIPSIBLINGS, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_POPUPWINDOW, WS_SIZEBOX, WS_VISIBLE,
-    XBUTTON1, XBUTTON2,
+    AdjustWindowRectEx, CloseClipboard, CreateWindowExW, DefWindowProcW, DestroyWindow,
+    DispatchMessageW, EmptyClipboard, GetDpiForWindow, GetFocus, GetMessageW, GetWindowLongPtrW,
+    LoadCursorW, OpenClipboard, PostMessageW, RegisterClassW, ReleaseCapture, SetCapture,
+    SetClipboardData, SetCursor, SetFocus, SetProcessDpiAwarenessContext, SetTimer,
+    SetWindowLongPtrW, SetWindowPos, TrackMouseEvent, TranslateMessage, UnregisterClassW,
+    CF_UNICODETEXT, CS_OWNDC, GET_XBUTTON_WPARAM, GWLP_USERDATA, HTCLIENT, IDC_ARROW, MSG,
+    SWP_NOMOVE, SWP_NOZORDER, TRACKMOUSEEVENT, WHEEL_DELTA, WM_CHAR, WM_CLOSE, WM_CREATE,
+    WM_DPICHANGED, WM_INPUTLANGCHANGE, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSELEAVE, WM_MOUSEMOVE, WM_MOUSEWHEEL,
+    WM_NCDESTROY, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETCURSOR, WM_SHOWWINDOW, WM_SIZE, WM_SYSCHAR,
+    WM_SYSKEYDOWN, WM_SYSKEYUP, WM_TIMER, WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW,
+    WS_CAPTION, WS_CHILD, WS_CLIPSIBLINGS, WS_MAXIMIZEBOX, WS_MINIMIZEBOX, WS_POPUPWINDOW,
+    WS_SIZEBOX, WS_VISIBLE, XBUTTON1, XBUTTON2,
 };
 
 use std::cell::{Cell, Ref, RefCell, RefMut};
@@ -25,6 +27,7 @@ use std::ffi::{c_void, OsStr};
 use std::os::windows::ffi::OsStrExt;
 use std::ptr::null_mut;
 use std::rc::Rc;
+use std::time::Duration;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, Win32WindowHandle,
@@ -35,8 +38,9 @@ const BV_WINDOW_MUST_CLOSE: UINT = WM_USER + 1;
 
 use crate::win::hook::{self, KeyboardHookHandle};
 use crate::{
-    Event, MouseButton, MouseCursor, MouseEvent, PhyPoint, PhySize, ScrollDelta, Size, WindowEvent,
-    WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy,
+    ClipboardData, ClipboardFormat, DragData, DropEffect, Error, Event, EventStatus, Icon,
+    MouseButton, MouseCursor, MouseEvent, PhyPoint, PhySize, Point, ScrollDelta, Size, UserEvent,
+    WindowEvent, WindowHandler, WindowInfo, WindowOpenOptions, WindowScalePolicy, WindowTask,
 };
 
 use super::cursor::cursor_to_lpcwstr;
@@ -84,6 +88,29 @@ impl WindowHandle {
     pub fn is_open(&self) -> bool {
         self.is_open.get()
     }
+
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy {}
+    }
+
+    pub fn run_on_window_thread(&self, _task: WindowTask) -> bool {
+        // TODO: Queue the task and run it from the window's event loop
+        false
+    }
+
+    pub fn is_on_window_thread(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone)]
+pub struct EventProxy {}
+
+impl EventProxy {
+    pub fn send_event(&self, _event: UserEvent) -> bool {
+        // TODO: Post a message to the window and deliver the event from its window procedure
+        false
+    }
 }
 
 unsafe impl HasRawWindowHandle for WindowHandle {
@@ -321,13 +348,17 @@ unsafe fn wnd_proc_inner(
             // Make sure to release the borrow before the DefWindowProc call
             {
                 let mut window = crate::Window::new(window_state.create_window());
+                let mut handler = window_state.handler.borrow_mut();
+                let handler = handler.as_mut().unwrap();
+
+                // The handler may want to keep the window open
+                let status =
+                    handler.on_event(&mut window, Event::Window(WindowEvent::CloseRequested));
+                if status == EventStatus::Captured {
+                    return Some(0);
+                }
 
-                window_state
-                    .handler
-                    .borrow_mut()
-                    .as_mut()
-                    .unwrap()
-                    .on_event(&mut window, Event::Window(WindowEvent::WillClose));
+                handler.on_event(&mut window, Event::Window(WindowEvent::WillClose));
             }
 
             // DestroyWindow(hwnd);
@@ -507,6 +538,7 @@ pub(super) struct WindowState {
     _drop_target: RefCell<Option<Rc<DropTarget>>>,
     scale_policy: WindowScalePolicy,
     dw_style: u32,
+    frame_interval: Cell<Duration>,
 
     // handle to the win32 keyboard hook
     // we don't need to read from this, just carry it around so the Drop impl can run
@@ -606,6 +638,34 @@ impl Window<'_> {
         window_handle
     }
 
+    pub fn try_open_parented<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        if !matches!(parent.raw_window_handle(), RawWindowHandle::Win32(_)) {
+            return Err(Error::InvalidParent);
+        }
+
+        // TODO: Return an error instead of panicking when creating the window fails
+        Ok(Self::open_parented::<P, H, B>(parent, options, build))
+    }
+
+    pub fn try_open_blocking<H, B>(options: WindowOpenOptions, build: B) -> Result<(), Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        // TODO: Return an error instead of panicking when creating the window fails
+        Self::open_blocking::<H, B>(options, build);
+        Ok(())
+    }
+
     pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
     where
         H: WindowHandler + 'static,
@@ -651,6 +711,7 @@ impl Window<'_> {
             };
 
             let window_info = WindowInfo::from_logical_size(options.size, scaling);
+            let frame_interval = options.frame_interval();
 
             let mut rect = RECT {
                 left: 0,
@@ -721,6 +782,7 @@ impl Window<'_> {
                 _drop_target: RefCell::new(None),
                 scale_policy: options.scale,
                 dw_style: flags,
+                frame_interval: Cell::new(frame_interval),
 
                 deferred_tasks: RefCell::new(VecDeque::with_capacity(4)),
 
@@ -773,8 +835,14 @@ impl Window<'_> {
             OleInitialize(null_mut());
             RegisterDragDrop(hwnd, Rc::as_ptr(&drop_target) as LPDROPTARGET);
 
+            // The handler may have changed the frame interval while it was being built
+            SetTimer(
+                hwnd,
+                WIN_FRAME_TIMER,
+                window_state.frame_interval.get().as_millis() as u32,
+                None,
+            );
             SetWindowLongPtrW(hwnd, GWLP_USERDATA, Rc::into_raw(window_state) as *const _ as _);
-            SetTimer(hwnd, WIN_FRAME_TIMER, 15, None);
 
             if let Some(mut new_rect) = new_rect {
                 // Convert this desired"client rectangle" size to the actual "window rectangle"
@@ -804,6 +872,54 @@ impl Window<'_> {
         }
     }
 
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy {}
+    }
+
+    pub fn request_redraw(&mut self) {
+        // TODO: Support `RedrawPolicy::OnDemand`, for now we're always drawing continuously
+    }
+
+    pub fn set_title(&mut self, _title: &str) {
+        // TODO: Call `SetWindowTextW()`
+    }
+
+    pub fn set_icon(&mut self, _icon: &Icon) {
+        // TODO: Create an `HICON` with `CreateIconIndirect()` and send it with `WM_SETICON`
+    }
+
+    pub fn position(&self) -> Point {
+        // TODO: Track the window's position
+        Point::new(0.0, 0.0)
+    }
+
+    pub fn set_position(&mut self, _position: Point) {
+        // TODO: Move the window
+    }
+
+    pub fn set_resizable(&mut self, _resizable: bool) {
+        // TODO: Update the window's style
+    }
+
+    pub fn set_min_size(&mut self, _min_size: Option<Size>) {
+        // TODO: Enforce the size constraints in `WM_GETMINMAXINFO`
+    }
+
+    pub fn set_max_size(&mut self, _max_size: Option<Size>) {
+        // TODO: Enforce the size constraints in `WM_GETMINMAXINFO`
+    }
+
+    pub fn set_aspect_ratio(&mut self, _aspect_ratio: Option<f64>) {
+        // TODO: Enforce the aspect ratio in `WM_SIZING`
+    }
+
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.state.frame_interval.set(interval);
+
+        // Setting a timer with the same ID replaces the existing one
+        unsafe { SetTimer(self.state.hwnd, WIN_FRAME_TIMER, interval.as_millis() as u32, None) };
+    }
+
     pub fn has_focus(&mut self) -> bool {
         let focused_window = unsafe { GetFocus() };
         focused_window == self.state.hwnd
@@ -815,6 +931,26 @@ impl Window<'_> {
         }
     }
 
+    pub fn request_clipboard_text(&mut self) {
+        // TODO: Read `CF_UNICODETEXT` from the clipboard
+    }
+
+    pub fn request_clipboard_data(&mut self, _format: ClipboardFormat) {
+        // TODO: Map the formats to clipboard formats
+    }
+
+    pub fn request_clipboard_formats(&mut self) {
+        // TODO: Enumerate the clipboard's formats
+    }
+
+    pub fn request_primary_selection_text(&mut self) {
+        // There's no primary selection on Windows
+    }
+
+    pub fn start_drag(&mut self, _data: DragData, _allowed_effects: &[DropEffect]) {
+        // TODO: Implement IDropSource and call DoDragDrop
+    }
+
     pub fn resize(&mut self, size: Size) {
         // To avoid reentrant event handler calls we'll defer the actual resizing until after the
         // event has been handled
@@ -851,6 +987,48 @@ unsafe impl HasRawDisplayHandle for Window<'_> {
     }
 }
 
-pub fn copy_to_clipboard(_data: &str) {
-    todo!()
+pub fn copy_to_clipboard(data: &str) {
+    // The clipboard takes a null terminated UTF-16 string stored in memory allocated with
+    // `GlobalAlloc()`
+    let text: Vec<u16> = data.encode_utf16().chain(std::iter::once(0)).collect();
+
+    unsafe {
+        if OpenClipboard(null_mut()) == FALSE {
+            return;
+        }
+
+        EmptyClipboard();
+
+        let memory = GlobalAlloc(GMEM_MOVEABLE, text.len() * std::mem::size_of::<u16>());
+        if !memory.is_null() {
+            let buffer = GlobalLock(memory) as *mut u16;
+            if buffer.is_null() {
+                GlobalFree(memory);
+            } else {
+                std::ptr::copy_nonoverlapping(text.as_ptr(), buffer, text.len());
+                GlobalUnlock(memory);
+
+                // The system owns the memory once it's on the clipboard
+                if SetClipboardData(CF_UNICODETEXT, memory).is_null() {
+                    GlobalFree(memory);
+                }
+            }
+        }
+
+        CloseClipboard();
+    }
+}
+
+pub fn copy_data_to_clipboard(data: Vec<ClipboardData>) {
+    // TODO: Write the other formats to the clipboard as well
+    for data in data {
+        if let ClipboardData::Text(text) = data {
+            copy_to_clipboard(&text);
+            return;
+        }
+    }
+}
+
+pub fn copy_to_primary_selection(_data: &str) {
+    // There's no primary selection on Windows
 }
@@ -1,12 +1,18 @@
+use std::any::Any;
 use std::marker::PhantomData;
+use std::sync::mpsc;
+use std::time::Duration;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
 };
 
-use crate::event::{Event, EventStatus};
-use crate::window_open_options::WindowOpenOptions;
-use crate::{MouseCursor, Size};
+use crate::event::{Event, EventStatus, UserEvent};
+use crate::window_open_options::{WindowOpenOptions, MIN_FRAME_INTERVAL};
+use crate::{ClipboardFormat, DragData, DropEffect, Error, Icon, MouseCursor, Point, Size};
+
+#[cfg(target_os = "linux")]
+use crate::EmbeddedWindowHandle;
 
 #[cfg(target_os = "macos")]
 use crate::macos as platform;
@@ -22,7 +28,7 @@ pub struct WindowHandle {
 }
 
 impl WindowHandle {
-    fn new(window_handle: platform::WindowHandle) -> Self {
+    pub(crate) fn new(window_handle: platform::WindowHandle) -> Self {
         Self { window_handle, phantom: PhantomData }
     }
 
@@ -36,6 +42,72 @@ impl WindowHandle {
     pub fn is_open(&self) -> bool {
         self.window_handle.is_open()
     }
+
+    /// Get a handle that can be used to send events to the window from any thread.
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy { event_proxy: self.window_handle.event_proxy() }
+    }
+
+    /// Run `task` on the thread the window's handler lives on. The window's event loop runs it in
+    /// between handling events, after which `task` gets dropped. Returns `false` if the window has
+    /// already been closed.
+    ///
+    /// This is currently only supported on Linux. On other platforms this always returns `false`.
+    pub fn run_on_window_thread<F>(&self, task: F) -> bool
+    where
+        F: FnOnce(&mut Window, &mut dyn WindowHandler) + Send + 'static,
+    {
+        self.window_handle.run_on_window_thread(Box::new(task))
+    }
+
+    /// The same as [`run_on_window_thread()`][Self::run_on_window_thread()], but waits for `task`
+    /// to finish and returns its result. Returns `None` if the window closed before `task` could
+    /// run.
+    ///
+    /// On Linux all windows' handlers run on the same thread, so this also returns `None` when
+    /// called from another window's handler, as waiting would deadlock.
+    pub fn run_on_window_thread_blocking<F, R>(&self, task: F) -> Option<R>
+    where
+        F: FnOnce(&mut Window, &mut dyn WindowHandler) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if self.window_handle.is_on_window_thread() {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let queued = self.run_on_window_thread(move |window, handler| {
+            let _ = tx.send(task(window, handler));
+        });
+
+        if queued {
+            // If the window closes before running the task, the sender gets dropped along with it
+            rx.recv().ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// A closure passed to [`WindowHandle::run_on_window_thread()`].
+pub(crate) type WindowTask = Box<dyn FnOnce(&mut Window, &mut dyn WindowHandler) + Send>;
+
+/// Sends [`Event::User`] events to a window's handler. Unlike [`Window`] and [`WindowHandle`] this
+/// can be sent to and used from any thread, for instance to notify the GUI of changes made by an
+/// audio thread without having to wait for the next frame.
+#[derive(Clone)]
+pub struct EventProxy {
+    event_proxy: platform::EventProxy,
+}
+
+impl EventProxy {
+    /// Send `event` to the window's handler as an [`Event::User`], waking up the window's event
+    /// loop if it's waiting for events. Returns `false` if the window has already been closed.
+    ///
+    /// This is currently only supported on Linux. On other platforms this always returns `false`.
+    pub fn send_event<T: Any + Send + Sync>(&self, event: T) -> bool {
+        self.event_proxy.send_event(UserEvent::new(event))
+    }
 }
 
 unsafe impl HasRawWindowHandle for WindowHandle {
@@ -78,6 +150,63 @@ impl<'a> Window<'a> {
         WindowHandle::new(window_handle)
     }
 
+    /// The same as [`open_parented()`][Self::open_parented()], but returns an error instead of
+    /// panicking when the window could not be opened. Plugins should prefer this, as a panic
+    /// would take the host down with it.
+    ///
+    /// Only the X11 backend reports every failure this way for now. On Windows and macOS an
+    /// unsupported parent handle results in [`Error::InvalidParent`], but other failures still
+    /// panic.
+    pub fn try_open_parented<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        platform::Window::try_open_parented::<P, H, B>(parent, options, build)
+            .map(WindowHandle::new)
+    }
+
+    /// Open a child window whose events are pumped by the host through the returned
+    /// [`EmbeddedWindowHandle`], for hosts that want the plugin's GUI to run on their own GUI
+    /// thread. Unlike [`open_parented()`][Self::open_parented()] this doesn't start a thread, and
+    /// the window handler is created, used, and dropped on the calling thread.
+    ///
+    /// This is only available on Linux. On the other platforms windows already run on the thread
+    /// they're opened from.
+    #[cfg(target_os = "linux")]
+    pub fn open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> EmbeddedWindowHandle
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+    {
+        let (window_handle, embedded_window) =
+            platform::Window::open_parented_embedded::<P, H, B>(parent, options, build);
+        EmbeddedWindowHandle::new(WindowHandle::new(window_handle), embedded_window)
+    }
+
+    /// The same as [`open_parented_embedded()`][Self::open_parented_embedded()], but returns an
+    /// error instead of panicking when the window could not be opened.
+    #[cfg(target_os = "linux")]
+    pub fn try_open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<EmbeddedWindowHandle, Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+    {
+        let (window_handle, embedded_window) =
+            platform::Window::try_open_parented_embedded::<P, H, B>(parent, options, build)?;
+        Ok(EmbeddedWindowHandle::new(WindowHandle::new(window_handle), embedded_window))
+    }
+
     pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
     where
         H: WindowHandler + 'static,
@@ -87,6 +216,56 @@ impl<'a> Window<'a> {
         platform::Window::open_blocking::<H, B>(options, build)
     }
 
+    /// The same as [`open_blocking()`][Self::open_blocking()], but returns an error instead of
+    /// panicking when the window could not be opened.
+    ///
+    /// Only the X11 backend reports failures this way for now. On Windows and macOS this still
+    /// panics when the window could not be opened.
+    pub fn try_open_blocking<H, B>(options: WindowOpenOptions, build: B) -> Result<(), Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        platform::Window::try_open_blocking::<H, B>(options, build)
+    }
+
+    /// Open another top-level window. If this window belongs to an [`Application`], then so does
+    /// the new window.
+    ///
+    /// This is only available on Linux for now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window could not be opened. All windows' handlers run on the same thread, so
+    /// that would close every window in the process. Use
+    /// [`try_open_window()`][Self::try_open_window()] to handle the error instead.
+    ///
+    /// [`Application`]: crate::Application
+    #[cfg(target_os = "linux")]
+    pub fn open_window<H, B>(&mut self, options: WindowOpenOptions, build: B) -> WindowHandle
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        WindowHandle::new(self.window.open_window::<H, B>(options, build))
+    }
+
+    /// The same as [`open_window()`][Self::open_window()], but returns an error instead of
+    /// panicking when the window could not be opened.
+    #[cfg(target_os = "linux")]
+    pub fn try_open_window<H, B>(
+        &mut self, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut Window) -> H,
+        B: Send + 'static,
+    {
+        self.window.try_open_window::<H, B>(options, build).map(WindowHandle::new)
+    }
+
     /// Close the window
     pub fn close(&mut self) {
         self.window.close();
@@ -98,10 +277,140 @@ impl<'a> Window<'a> {
         self.window.resize(size);
     }
 
+    /// Change the window's title. Titles can contain any Unicode characters.
+    ///
+    /// This is currently only supported on Linux.
+    pub fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    /// Change the window's icon to an image made up of `width` by `height` 8-bit RGBA pixels. See
+    /// [`Icon::from_rgba()`][crate::Icon::from_rgba()] for the layout of `rgba`. The icon is left
+    /// unchanged if `rgba` doesn't contain exactly `width * height * 4` bytes.
+    ///
+    /// This is currently only supported on Linux.
+    pub fn set_icon(&mut self, width: u32, height: u32, rgba: &[u8]) {
+        if rgba.len() == width as usize * height as usize * 4 {
+            self.window.set_icon(&Icon::from_rgba(width, height, rgba.to_vec()));
+        }
+    }
+
+    /// The logical position of the top left corner of the window's frame, relative to the screen
+    /// for top-level windows and relative to the parent for child windows. This is where the
+    /// window was last reported to be, so it may lag behind a call to
+    /// [`set_position()`][Self::set_position()].
+    ///
+    /// This is currently only supported on Linux.
+    pub fn position(&self) -> Point {
+        self.window.position()
+    }
+
+    /// Move the top left corner of the window's frame to the given logical position. Once the
+    /// window has moved, the handler receives a [`WindowEvent::Moved`][crate::WindowEvent::Moved]
+    /// event.
+    pub fn set_position(&mut self, position: Point) {
+        self.window.set_position(position);
+    }
+
+    /// Allow or prevent the user from resizing the window. See
+    /// [`WindowOpenOptions::resizable`][crate::WindowOpenOptions::resizable].
+    ///
+    /// The size constraints are currently only supported on Linux.
+    pub fn set_resizable(&mut self, resizable: bool) {
+        self.window.set_resizable(resizable);
+    }
+
+    /// Change the smallest logical size the user can resize the window to, or remove the limit.
+    pub fn set_min_size(&mut self, min_size: Option<Size>) {
+        self.window.set_min_size(min_size);
+    }
+
+    /// Change the largest logical size the user can resize the window to, or remove the limit.
+    pub fn set_max_size(&mut self, max_size: Option<Size>) {
+        self.window.set_max_size(max_size);
+    }
+
+    /// Change the ratio between the window's width and height that's kept when the user resizes
+    /// the window, or let the user resize the window freely.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<f64>) {
+        self.window.set_aspect_ratio(aspect_ratio);
+    }
+
     pub fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
         self.window.set_mouse_cursor(cursor);
     }
 
+    /// Request the clipboard's contents as text. Reading the clipboard may require a round trip
+    /// to another application, so the text is delivered asynchronously through an
+    /// [`Event::Clipboard`] event containing a [`ClipboardEvent::Text`][crate::ClipboardEvent::Text].
+    ///
+    /// Reading the clipboard is only implemented on Linux for now. On Windows and macOS no event
+    /// is sent in response.
+    pub fn request_clipboard_text(&mut self) {
+        self.window.request_clipboard_text();
+    }
+
+    /// Request the clipboard's contents in the given format. The result is delivered
+    /// asynchronously through an [`Event::Clipboard`] event containing a
+    /// [`ClipboardEvent::Data`][crate::ClipboardEvent::Data].
+    ///
+    /// Reading the clipboard is only implemented on Linux for now. On Windows and macOS no event
+    /// is sent in response.
+    pub fn request_clipboard_data(&mut self, format: ClipboardFormat) {
+        self.window.request_clipboard_data(format);
+    }
+
+    /// Request the formats the clipboard's contents are available in. The result is delivered
+    /// asynchronously through an [`Event::Clipboard`] event containing a
+    /// [`ClipboardEvent::Formats`][crate::ClipboardEvent::Formats].
+    ///
+    /// Reading the clipboard is only implemented on Linux for now. On Windows and macOS no event
+    /// is sent in response.
+    pub fn request_clipboard_formats(&mut self) {
+        self.window.request_clipboard_formats();
+    }
+
+    /// Request the `PRIMARY` selection's contents as text, for pasting with a middle click. The
+    /// result is delivered asynchronously through an [`Event::Clipboard`] event containing a
+    /// [`ClipboardEvent::PrimaryText`][crate::ClipboardEvent::PrimaryText].
+    ///
+    /// Only Linux has a primary selection. Other platforms won't send any events in response.
+    pub fn request_primary_selection_text(&mut self) {
+        self.window.request_primary_selection_text();
+    }
+
+    /// Start dragging `data` out of the window. This should be called while a mouse button is held
+    /// down, and the drag ends when that button is released. The first of the `allowed_effects` is
+    /// the one that's requested from the drop target. Once the drag has ended, the handler receives
+    /// a [`MouseEvent::DragFinished`][crate::MouseEvent::DragFinished] event with the effect the
+    /// drop target settled on, or with no effect if the drag could not be started. The release of
+    /// the mouse button is still delivered as a
+    /// [`MouseEvent::ButtonReleased`][crate::MouseEvent::ButtonReleased] event.
+    ///
+    /// This is currently only implemented on Linux.
+    pub fn start_drag(&mut self, data: DragData, allowed_effects: &[DropEffect]) {
+        self.window.start_drag(data, allowed_effects);
+    }
+
+    /// Get a handle that can be used to send events to this window from any thread.
+    pub fn event_proxy(&self) -> EventProxy {
+        EventProxy { event_proxy: self.window.event_proxy() }
+    }
+
+    /// Ask for [`on_frame()`][crate::WindowHandler::on_frame()] to be called on the next tick of the
+    /// frame clock. This only needs to be called when using [`RedrawPolicy::OnDemand`].
+    ///
+    /// [`RedrawPolicy::OnDemand`]: crate::RedrawPolicy::OnDemand
+    pub fn request_redraw(&mut self) {
+        self.window.request_redraw();
+    }
+
+    /// Change how long to wait between two [`on_frame()`][crate::WindowHandler::on_frame()] calls.
+    /// Intervals shorter than a millisecond are rounded up to one millisecond.
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.window.set_frame_interval(interval.max(MIN_FRAME_INTERVAL));
+    }
+
     pub fn has_focus(&mut self) -> bool {
         self.window.has_focus()
     }
@@ -97,6 +97,32 @@ impl PhyPoint {
     }
 }
 
+/// A rectangle in actual physical coordinates
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PhyRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PhyRect {
+    /// Create a new rectangle in actual physical coordinates
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// The rectangle's top left corner
+    pub fn origin(&self) -> PhyPoint {
+        PhyPoint { x: self.x, y: self.y }
+    }
+
+    /// The rectangle's size
+    pub fn size(&self) -> PhySize {
+        PhySize { width: self.width, height: self.height }
+    }
+}
+
 /// A size in logical coordinates
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Size {
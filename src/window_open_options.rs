@@ -1,4 +1,14 @@
-use crate::Size;
+use std::time::Duration;
+
+use crate::{Icon, Point, Size};
+
+/// The interval between two [`on_frame()`][crate::WindowHandler::on_frame()] calls if no frame
+/// rate was specified.
+const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_millis(15);
+
+/// The shortest interval between two [`on_frame()`][crate::WindowHandler::on_frame()] calls.
+/// Anything shorter would keep the event loop from ever going to sleep.
+pub(crate) const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(1);
 
 /// The dpi scaling policy of the window
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -9,7 +19,55 @@ pub enum WindowScalePolicy {
     ScaleFactor(f64),
 }
 
-/// The options for opening a new window
+/// What decides when [`on_frame()`][crate::WindowHandler::on_frame()] gets called.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameClock {
+    /// Call `on_frame()` on a timer running at the window's frame rate.
+    Timer,
+    /// Call `on_frame()` once for every refresh of the display the window is shown on, so frames
+    /// line up with the display instead of drifting against it. The frame rate is ignored in this
+    /// mode. Falls back to [`FrameClock::Timer`] where this isn't supported.
+    ///
+    /// This is currently only supported on Linux, through the X Present extension.
+    Vsync,
+}
+
+/// Whether [`on_frame()`][crate::WindowHandler::on_frame()] gets called continuously or only when
+/// the window needs to be redrawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedrawPolicy {
+    /// Call `on_frame()` for every tick of the frame clock.
+    Continuous,
+    /// Only call `on_frame()` on the next tick of the frame clock after
+    /// [`Window::request_redraw()`][crate::Window::request_redraw()] has been called, or after the
+    /// system has asked for (part of) the window to be repainted. The window's event loop sleeps in
+    /// between, which saves a lot of CPU time when many windows are open but only a few of them are
+    /// animating.
+    ///
+    /// This is currently only supported on Linux. Other platforms keep drawing continuously.
+    OnDemand,
+}
+
+/// Where a window is placed when it's opened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowPosition {
+    /// Let the window manager decide where to place top-level windows. Child windows are placed in
+    /// their parent's top left corner.
+    Default,
+    /// Place the top left corner of the window's frame at this logical position, relative to the
+    /// screen for top-level windows and relative to the parent for child windows.
+    Point(Point),
+    /// Center the window on the screen. With multiple monitors, that's the monitor holding the
+    /// window it was opened from or the mouse pointer. Child windows are centered in their parent.
+    CenteredOnScreen,
+    /// Center the window on the window it was opened from. That's the parent for child windows,
+    /// and the window whose handler called [`Window::open_window()`][crate::Window::open_window()]
+    /// for top-level windows. Other top-level windows are centered on the screen.
+    CenteredOnParent,
+}
+
+/// The options for opening a new window. Fields that don't matter to you can be left at their
+/// defaults using `..Default::default()`.
 pub struct WindowOpenOptions {
     pub title: String,
 
@@ -22,8 +80,78 @@ pub struct WindowOpenOptions {
     /// The dpi scaling policy
     pub scale: WindowScalePolicy,
 
+    /// The window's icon, or `None` to use the system's default icon. This can be changed later
+    /// using [`Window::set_icon()`][crate::Window::set_icon()].
+    ///
+    /// This is currently only supported on Linux.
+    pub icon: Option<Icon>,
+
+    /// Where the window is placed when it's opened.
+    ///
+    /// This is currently only supported on Linux.
+    pub position: WindowPosition,
+
+    /// Whether the user can resize the window. This and the other size constraints only apply to
+    /// top-level windows, as the size of a child window is up to its parent.
+    pub resizable: bool,
+
+    /// The smallest logical size the user can resize the window to.
+    pub min_size: Option<Size>,
+
+    /// The largest logical size the user can resize the window to.
+    pub max_size: Option<Size>,
+
+    /// If set, the window's width divided by its height is kept at this ratio when the user
+    /// resizes the window.
+    pub aspect_ratio: Option<f64>,
+
+    /// How many times per second [`on_frame()`][crate::WindowHandler::on_frame()] should be
+    /// called, or `None` for the default of roughly 66 frames per second. Rates above 1000 frames
+    /// per second are capped, and rates that aren't positive and finite are ignored. This can be
+    /// changed later using [Window::set_frame_interval](`crate::Window::set_frame_interval()`).
+    pub frame_rate: Option<f64>,
+
+    /// What paces the calls to [`on_frame()`][crate::WindowHandler::on_frame()].
+    pub frame_clock: FrameClock,
+
+    /// When [`on_frame()`][crate::WindowHandler::on_frame()] should be called.
+    pub redraw_policy: RedrawPolicy,
+
     /// If provided, then an OpenGL context will be created for this window. You'll be able to
     /// access this context through [crate::Window::gl_context].
     #[cfg(feature = "opengl")]
     pub gl_config: Option<crate::gl::GlConfig>,
 }
+
+impl WindowOpenOptions {
+    /// The interval between frames resulting from [`frame_rate`][Self::frame_rate].
+    pub(crate) fn frame_interval(&self) -> Duration {
+        match self.frame_rate {
+            Some(frame_rate) if frame_rate.is_finite() && frame_rate > 0.0 => {
+                Duration::from_secs_f64(1.0 / frame_rate).max(MIN_FRAME_INTERVAL)
+            }
+            _ => DEFAULT_FRAME_INTERVAL,
+        }
+    }
+}
+
+impl Default for WindowOpenOptions {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            size: Size::new(500.0, 400.0),
+            scale: WindowScalePolicy::SystemScaleFactor,
+            icon: None,
+            position: WindowPosition::Default,
+            resizable: true,
+            min_size: None,
+            max_size: None,
+            aspect_ratio: None,
+            frame_rate: None,
+            frame_clock: FrameClock::Timer,
+            redraw_policy: RedrawPolicy::Continuous,
+            #[cfg(feature = "opengl")]
+            gl_config: None,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::mpsc;
+
+use super::dispatcher;
+use super::window::{Window, WindowHandle};
+use crate::{Error, WindowHandler, WindowOpenOptions};
+
+/// Every window that belongs to the application holds on to a clone of the application's
+/// `keep_alive` sender, so the receiving end disconnects once the last of those windows has
+/// closed.
+pub struct Application {
+    keep_alive: mpsc::Sender<()>,
+    closed: mpsc::Receiver<()>,
+}
+
+impl Application {
+    pub fn new() -> Self {
+        let (keep_alive, closed) = mpsc::channel();
+
+        Self { keep_alive, closed }
+    }
+
+    pub fn open_window<H, B>(&mut self, options: WindowOpenOptions, build: B) -> WindowHandle
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        self.try_open_window::<H, B>(options, build).unwrap()
+    }
+
+    pub fn try_open_window<H, B>(
+        &mut self, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        Window::open_top_level(options, build, Some(self.keep_alive.clone()), None)
+    }
+
+    pub fn run(self) {
+        let Self { keep_alive, closed } = self;
+        drop(keep_alive);
+
+        // The windows' handlers run on the dispatcher thread, so we can't wait there
+        if !dispatcher::is_dispatcher_thread() {
+            let _ = closed.recv();
+        }
+    }
+}
@@ -0,0 +1,842 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, EventMask, PropMode, Property, PropertyNotifyEvent,
+    SelectionClearEvent, SelectionNotifyEvent, SelectionRequestEvent, Timestamp, Window as XWindow,
+    SELECTION_NOTIFY_EVENT,
+};
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::NONE;
+
+use super::XcbConnection;
+use crate::{ClipboardData, ClipboardEvent, ClipboardFormat};
+
+thread_local! {
+    /// Data passed to [`copy_to_clipboard()`] and friends that hasn't been claimed by the window
+    /// whose handler copied it yet.
+    static PENDING_COPIES: RefCell<Vec<(Selection, Vec<ClipboardData>)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Requestors that stop responding halfway through an `INCR` transfer are dropped after this long.
+const INCR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long we'll wait on the selection owner before giving up on a conversion we requested.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Stores `data` so that once the window handler calling this returns, its window can take
+/// ownership of the `CLIPBOARD` selection. On X11 this thus needs to be called from within a
+/// window's handler.
+pub fn copy_to_clipboard(data: &str) {
+    copy_data_to_clipboard(vec![ClipboardData::Text(data.to_owned())]);
+}
+
+/// The same as [`copy_to_clipboard()`], but for any number of representations of the same data.
+pub fn copy_data_to_clipboard(data: Vec<ClipboardData>) {
+    PENDING_COPIES.with(|pending| pending.borrow_mut().push((Selection::Clipboard, data)));
+}
+
+/// The same as [`copy_to_clipboard()`], but for the `PRIMARY` selection.
+pub fn copy_to_primary_selection(data: &str) {
+    let data = vec![ClipboardData::Text(data.to_owned())];
+    PENDING_COPIES.with(|pending| pending.borrow_mut().push((Selection::Primary, data)));
+}
+
+pub(super) fn take_pending_copies() -> Vec<(Selection, Vec<ClipboardData>)> {
+    PENDING_COPIES.with(|pending| std::mem::take(&mut *pending.borrow_mut()))
+}
+
+/// The selections we can own and read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Selection {
+    /// The `CLIPBOARD` selection, used for explicit copy and paste.
+    Clipboard,
+    /// The `PRIMARY` selection, which is set by selecting text and pasted with a middle click.
+    Primary,
+    /// The `XdndSelection` selection, which holds the data while dragging from one of our windows.
+    Drag,
+}
+
+impl Selection {
+    fn from_atom(xcb_connection: &XcbConnection, atom: Atom) -> Option<Self> {
+        if atom == xcb_connection.atoms.CLIPBOARD {
+            Some(Selection::Clipboard)
+        } else if atom == AtomEnum::PRIMARY.into() {
+            Some(Selection::Primary)
+        } else if atom == xcb_connection.atoms.XdndSelection {
+            Some(Selection::Drag)
+        } else {
+            None
+        }
+    }
+
+    fn atom(self, xcb_connection: &XcbConnection) -> Atom {
+        match self {
+            Selection::Clipboard => xcb_connection.atoms.CLIPBOARD,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+            Selection::Drag => xcb_connection.atoms.XdndSelection,
+        }
+    }
+}
+
+/// The data we'll send in response to a conversion request for `target`.
+struct Representation {
+    target: Atom,
+    /// The type the property is written with. This is usually the same as the target.
+    data_type: Atom,
+    data: Rc<[u8]>,
+}
+
+/// An in-progress `INCR` transfer for selection contents that don't fit in a single request.
+struct IncrTransfer {
+    requestor: XWindow,
+    property: Atom,
+    target: Atom,
+    data: Rc<[u8]>,
+    offset: usize,
+    last_activity: Instant,
+}
+
+/// What the handler asked for when requesting the clipboard's contents. This decides how the
+/// converted data gets delivered.
+enum ReadRequest {
+    Text,
+    Data(ClipboardFormat),
+    Formats,
+    PrimaryText,
+}
+
+impl ReadRequest {
+    fn selection(&self) -> Selection {
+        match self {
+            ReadRequest::PrimaryText => Selection::Primary,
+            _ => Selection::Clipboard,
+        }
+    }
+}
+
+/// A conversion of a selection we've asked its owner for.
+struct SelectionRead {
+    request: ReadRequest,
+    target: Atom,
+    /// The timestamp the conversion was requested with, reused if we need to retry it.
+    time: Timestamp,
+    /// The data received so far, if the owner is sending it to us through an `INCR` transfer.
+    incr_data: Option<Vec<u8>>,
+    last_activity: Instant,
+}
+
+/// The contents of the `CLIPBOARD` and `PRIMARY` selections while they're owned by our window, and
+/// the machinery to hand them out to other clients and to read them from other clients.
+pub(crate) struct Clipboard {
+    window: XWindow,
+
+    /// The targets we can convert the `CLIPBOARD` selection to. This is empty when we don't own
+    /// the selection.
+    clipboard: Vec<Representation>,
+    /// The same, but for the `PRIMARY` selection.
+    primary: Vec<Representation>,
+    /// And for the data being dragged from our window.
+    drag: Vec<Representation>,
+    transfers: Vec<IncrTransfer>,
+
+    /// The conversion we're currently waiting on. Conversions are requested one at a time since
+    /// they all share the same property on our window.
+    read: Option<SelectionRead>,
+    /// Conversions that were requested while another one was still in progress, along with the
+    /// timestamps of the input that caused them.
+    queued_reads: VecDeque<(ReadRequest, Timestamp)>,
+    /// Empty responses for conversions we failed to request, waiting to be delivered to the
+    /// handler so it isn't left waiting for them.
+    failed_reads: VecDeque<ClipboardEvent>,
+}
+
+impl Clipboard {
+    pub fn new(window: XWindow) -> Self {
+        Self {
+            window,
+            clipboard: Vec::new(),
+            primary: Vec::new(),
+            drag: Vec::new(),
+            transfers: Vec::new(),
+            read: None,
+            queued_reads: VecDeque::new(),
+            failed_reads: VecDeque::new(),
+        }
+    }
+
+    fn representations(&mut self, selection: Selection) -> &mut Vec<Representation> {
+        match selection {
+            Selection::Clipboard => &mut self.clipboard,
+            Selection::Primary => &mut self.primary,
+            Selection::Drag => &mut self.drag,
+        }
+    }
+
+    /// The targets we can currently convert `selection` to, in order of preference.
+    pub fn targets(&mut self, selection: Selection) -> Vec<Atom> {
+        self.representations(selection).iter().map(|representation| representation.target).collect()
+    }
+
+    /// Take ownership of a selection and serve `data` to anyone asking for it. `time` is the
+    /// timestamp of the input that caused this, as ICCCM forbids using `CurrentTime` here.
+    pub fn set_contents(
+        &mut self, xcb_connection: &XcbConnection, selection: Selection, data: Vec<ClipboardData>,
+        time: Timestamp,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = &xcb_connection.conn;
+        let window = self.window;
+
+        let mut representations = Vec::new();
+        for data in data {
+            add_representations(xcb_connection, &mut representations, data)?;
+        }
+
+        let selection_atom = selection.atom(xcb_connection);
+        conn.set_selection_owner(window, selection_atom, time)?;
+        if conn.get_selection_owner(selection_atom)?.reply()?.owner == window {
+            *self.representations(selection) = representations;
+        } else {
+            self.representations(selection).clear();
+        }
+
+        Ok(())
+    }
+
+    pub fn handle_selection_clear(
+        &mut self, xcb_connection: &XcbConnection, event: &SelectionClearEvent,
+    ) {
+        if let Some(selection) = Selection::from_atom(xcb_connection, event.selection) {
+            self.representations(selection).clear();
+        }
+    }
+
+    pub fn handle_selection_request(
+        &mut self, xcb_connection: &XcbConnection, event: &SelectionRequestEvent,
+    ) -> Result<(), Box<dyn Error>> {
+        // Obsolete clients don't specify a property, in which case the target doubles as one
+        let property = if event.property == NONE { event.target } else { event.property };
+
+        let converted = match Selection::from_atom(xcb_connection, event.selection) {
+            Some(selection) if event.target == xcb_connection.atoms.MULTIPLE => {
+                self.convert_multiple(xcb_connection, selection, event.requestor, property)?
+            }
+            Some(selection) => {
+                self.convert(xcb_connection, selection, event.requestor, property, event.target)?
+            }
+            None => false,
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property: if converted { property } else { NONE },
+        };
+        xcb_connection.conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)?;
+        xcb_connection.conn.flush()?;
+
+        Ok(())
+    }
+
+    /// Write the selection's contents to the requestor's property in the format requested
+    /// through `target`. Returns `false` if we can't convert to that target.
+    fn convert(
+        &mut self, xcb_connection: &XcbConnection, selection: Selection, requestor: XWindow,
+        property: Atom, target: Atom,
+    ) -> Result<bool, Box<dyn Error>> {
+        let conn = &xcb_connection.conn;
+        let atoms = &xcb_connection.atoms;
+
+        let representations = self.representations(selection);
+        if representations.is_empty() {
+            return Ok(false);
+        }
+
+        if target == atoms.TARGETS {
+            let targets: Vec<Atom> = [atoms.TARGETS, atoms.MULTIPLE]
+                .iter()
+                .copied()
+                .chain(representations.iter().map(|r| r.target))
+                .collect();
+            conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                AtomEnum::ATOM,
+                &targets,
+            )?;
+            return Ok(true);
+        }
+
+        let (data, data_type) = match representations.iter().find(|r| r.target == target) {
+            Some(representation) => (Rc::clone(&representation.data), representation.data_type),
+            None => return Ok(false),
+        };
+
+        if data.len() <= max_chunk_size(xcb_connection) {
+            conn.change_property8(PropMode::REPLACE, requestor, property, data_type, &data)?;
+        } else {
+            // The requestor signals that it's ready for the next chunk by deleting the property, so
+            // we need to listen for property changes on its window
+            xcb_connection.select_requestor(requestor)?;
+            self.end_transfers(xcb_connection, |t| {
+                t.requestor == requestor && t.property == property
+            })?;
+            conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                atoms.INCR,
+                &[data.len() as u32],
+            )?;
+
+            self.transfers.push(IncrTransfer {
+                requestor,
+                property,
+                target: data_type,
+                data,
+                offset: 0,
+                last_activity: Instant::now(),
+            });
+        }
+
+        Ok(true)
+    }
+
+    /// Handle a `MULTIPLE` conversion. The requestor's property contains pairs of targets and
+    /// properties to convert the selection to, and pairs we can't convert have their property
+    /// replaced with `None`.
+    fn convert_multiple(
+        &mut self, xcb_connection: &XcbConnection, selection: Selection, requestor: XWindow,
+        property: Atom,
+    ) -> Result<bool, Box<dyn Error>> {
+        let reply = xcb_connection
+            .conn
+            .get_property(false, requestor, property, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+        let mut pairs: Vec<Atom> = match reply.value32() {
+            Some(pairs) => pairs.collect(),
+            None => return Ok(false),
+        };
+
+        for pair in pairs.chunks_exact_mut(2) {
+            let (target, pair_property) = (pair[0], pair[1]);
+            if target == xcb_connection.atoms.MULTIPLE
+                || !self.convert(xcb_connection, selection, requestor, pair_property, target)?
+            {
+                pair[1] = NONE;
+            }
+        }
+
+        xcb_connection.conn.change_property32(
+            PropMode::REPLACE,
+            requestor,
+            property,
+            reply.type_,
+            &pairs,
+        )?;
+
+        Ok(true)
+    }
+
+    /// Ask the clipboard manager to take over the contents of the `CLIPBOARD` selection so they
+    /// survive our window closing. The manager will then request every target we offer like any
+    /// other client would. Returns `false` if there's nothing to hand off or if there's no
+    /// clipboard manager, in which case there's no need to wait for a response.
+    pub fn start_save(
+        &mut self, xcb_connection: &XcbConnection, time: Timestamp,
+    ) -> Result<bool, Box<dyn Error>> {
+        let conn = &xcb_connection.conn;
+        let atoms = &xcb_connection.atoms;
+
+        if self.clipboard.is_empty()
+            || conn.get_selection_owner(atoms.CLIPBOARD_MANAGER)?.reply()?.owner == NONE
+        {
+            return Ok(false);
+        }
+
+        // Any conversion we were waiting on would end up in the same property
+        self.read = None;
+        self.queued_reads.clear();
+
+        let targets: Vec<Atom> = self.clipboard.iter().map(|r| r.target).collect();
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            atoms.BASEVIEW_SELECTION,
+            AtomEnum::ATOM,
+            &targets,
+        )?;
+        conn.convert_selection(
+            self.window,
+            atoms.CLIPBOARD_MANAGER,
+            atoms.SAVE_TARGETS,
+            atoms.BASEVIEW_SELECTION,
+            time,
+        )?;
+        conn.flush()?;
+
+        Ok(true)
+    }
+
+    /// Whether this is the clipboard manager's response to [`Self::start_save()`].
+    pub fn is_save_finished(
+        &self, xcb_connection: &XcbConnection, event: &SelectionNotifyEvent,
+    ) -> bool {
+        event.requestor == self.window
+            && event.selection == xcb_connection.atoms.CLIPBOARD_MANAGER
+            && event.target == xcb_connection.atoms.SAVE_TARGETS
+    }
+
+    /// Continue any `INCR` transfer this property change is part of. Returns an event for the
+    /// handler if this finished reading the selection's contents.
+    pub fn handle_property_notify(
+        &mut self, xcb_connection: &XcbConnection, event: &PropertyNotifyEvent,
+    ) -> Result<Option<ClipboardEvent>, Box<dyn Error>> {
+        if event.window == self.window {
+            self.continue_read(xcb_connection, event)
+        } else {
+            self.continue_transfer(xcb_connection, event)?;
+            Ok(None)
+        }
+    }
+
+    /// Send the next chunk of an `INCR` transfer once the requestor has deleted its property.
+    fn continue_transfer(
+        &mut self, xcb_connection: &XcbConnection, event: &PropertyNotifyEvent,
+    ) -> Result<(), Box<dyn Error>> {
+        self.end_transfers(xcb_connection, |t| t.last_activity.elapsed() >= INCR_TIMEOUT)?;
+
+        if event.state != Property::DELETE {
+            return Ok(());
+        }
+
+        let index = match self
+            .transfers
+            .iter()
+            .position(|t| t.requestor == event.window && t.property == event.atom)
+        {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let conn = &xcb_connection.conn;
+        let chunk_size = max_chunk_size(xcb_connection);
+        let transfer = &mut self.transfers[index];
+
+        // An empty chunk marks the end of the transfer
+        let end = (transfer.offset + chunk_size).min(transfer.data.len());
+        conn.change_property8(
+            PropMode::REPLACE,
+            transfer.requestor,
+            transfer.property,
+            transfer.target,
+            &transfer.data[transfer.offset..end],
+        )?;
+
+        if transfer.offset == end {
+            let (requestor, property) = (transfer.requestor, transfer.property);
+            self.end_transfers(xcb_connection, |t| {
+                t.requestor == requestor && t.property == property
+            })?;
+        } else {
+            transfer.offset = end;
+            transfer.last_activity = Instant::now();
+        }
+
+        conn.flush()?;
+
+        Ok(())
+    }
+
+    /// Stop the `INCR` transfers matching `predicate`, and stop listening for property changes on
+    /// their requestors' windows if there are no other transfers to them.
+    fn end_transfers(
+        &mut self, xcb_connection: &XcbConnection, mut predicate: impl FnMut(&IncrTransfer) -> bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut result = Ok(());
+        self.transfers.retain(|transfer| {
+            if !predicate(transfer) {
+                return true;
+            }
+
+            if let Err(err) = xcb_connection.release_requestor(transfer.requestor) {
+                result = Err(err);
+            }
+
+            false
+        });
+
+        result
+    }
+
+    /// Stop all `INCR` transfers, for when the window gets destroyed.
+    pub fn cancel_transfers(
+        &mut self, xcb_connection: &XcbConnection,
+    ) -> Result<(), Box<dyn Error>> {
+        self.end_transfers(xcb_connection, |_| true)
+    }
+
+    /// Ask the owner of the `CLIPBOARD` selection for its contents as text. The text is delivered
+    /// as a [`ClipboardEvent::Text`] once the owner has responded.
+    pub fn request_text(&mut self, xcb_connection: &XcbConnection, time: Timestamp) {
+        self.queued_reads.push_back((ReadRequest::Text, time));
+        self.start_next_read(xcb_connection);
+    }
+
+    /// Ask the owner of the `CLIPBOARD` selection for its contents in the given format. The data
+    /// is delivered as a [`ClipboardEvent::Data`] once the owner has responded.
+    pub fn request_data(
+        &mut self, xcb_connection: &XcbConnection, format: ClipboardFormat, time: Timestamp,
+    ) {
+        self.queued_reads.push_back((ReadRequest::Data(format), time));
+        self.start_next_read(xcb_connection);
+    }
+
+    /// Ask the owner of the `CLIPBOARD` selection which formats it can provide. These are
+    /// delivered as a [`ClipboardEvent::Formats`] once the owner has responded.
+    pub fn request_formats(&mut self, xcb_connection: &XcbConnection, time: Timestamp) {
+        self.queued_reads.push_back((ReadRequest::Formats, time));
+        self.start_next_read(xcb_connection);
+    }
+
+    /// Ask the owner of the `PRIMARY` selection for its contents as text. The text is delivered
+    /// as a [`ClipboardEvent::PrimaryText`] once the owner has responded.
+    pub fn request_primary_text(&mut self, xcb_connection: &XcbConnection, time: Timestamp) {
+        self.queued_reads.push_back((ReadRequest::PrimaryText, time));
+        self.start_next_read(xcb_connection);
+    }
+
+    /// Request the next queued conversion if we're not already waiting on one. Requests that fail
+    /// are answered with an empty response, after which we move on to the next one.
+    fn start_next_read(&mut self, xcb_connection: &XcbConnection) {
+        while self.read.is_none() {
+            let (request, time) = match self.queued_reads.pop_front() {
+                Some(queued_read) => queued_read,
+                None => return,
+            };
+
+            let atoms = &xcb_connection.atoms;
+            let target = match &request {
+                ReadRequest::Text
+                | ReadRequest::PrimaryText
+                | ReadRequest::Data(ClipboardFormat::Text) => Ok(atoms.UTF8_STRING),
+                ReadRequest::Data(ClipboardFormat::UriList) => Ok(atoms.URI_LIST),
+                ReadRequest::Data(ClipboardFormat::Png) => Ok(atoms.IMAGE_PNG),
+                ReadRequest::Data(ClipboardFormat::Custom(mime)) => {
+                    xcb_connection.intern_atom(mime)
+                }
+                ReadRequest::Formats => Ok(atoms.TARGETS),
+            };
+
+            let result = target.and_then(|target| {
+                self.convert_selection(xcb_connection, request.selection(), target, time)?;
+                Ok(target)
+            });
+            match result {
+                Ok(target) => {
+                    self.read = Some(SelectionRead {
+                        request,
+                        target,
+                        time,
+                        incr_data: None,
+                        last_activity: Instant::now(),
+                    });
+                }
+                Err(_) => self.failed_reads.push_back(read_event(xcb_connection, request, None)),
+            }
+        }
+    }
+
+    /// The next empty response for a conversion we failed to request, if any.
+    pub fn take_failed_read(&mut self) -> Option<ClipboardEvent> {
+        self.failed_reads.pop_front()
+    }
+
+    /// Whether any of those empty responses are still waiting to be delivered.
+    pub fn has_failed_reads(&self) -> bool {
+        !self.failed_reads.is_empty()
+    }
+
+    /// Ask the owner of `selection` to convert it to `target` and store the result on our window.
+    fn convert_selection(
+        &self, xcb_connection: &XcbConnection, selection: Selection, target: Atom, time: Timestamp,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = &xcb_connection.conn;
+        let atoms = &xcb_connection.atoms;
+
+        conn.convert_selection(
+            self.window,
+            selection.atom(xcb_connection),
+            target,
+            atoms.BASEVIEW_SELECTION,
+            time,
+        )?;
+        conn.flush()?;
+
+        Ok(())
+    }
+
+    /// Handle the selection owner's response to one of our conversion requests. Returns an event
+    /// for the handler if the selection's contents are now known.
+    pub fn handle_selection_notify(
+        &mut self, xcb_connection: &XcbConnection, event: &SelectionNotifyEvent,
+    ) -> Result<Option<ClipboardEvent>, Box<dyn Error>> {
+        match &self.read {
+            Some(read) if event.requestor == self.window && event.target == read.target => (),
+            _ => return Ok(None),
+        };
+
+        if event.property == NONE {
+            // Not every owner supports `UTF8_STRING`, so we'll retry with plain old `STRING`
+            let read = self.read.as_ref().unwrap();
+            if read.target == xcb_connection.atoms.UTF8_STRING {
+                let (selection, time) = (read.request.selection(), read.time);
+                let target = AtomEnum::STRING.into();
+                if self.convert_selection(xcb_connection, selection, target, time).is_ok() {
+                    let read = self.read.as_mut().unwrap();
+                    read.target = target;
+                    read.last_activity = Instant::now();
+
+                    return Ok(None);
+                }
+            }
+
+            return Ok(self.finish_read(xcb_connection, None));
+        }
+
+        let reply = xcb_connection
+            .conn
+            .get_property(true, self.window, event.property, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        if reply.type_ == xcb_connection.atoms.INCR {
+            // Deleting the property (which `get_property()` just did) tells the owner to start
+            // sending chunks, which we'll receive through `PropertyNotify` events
+            if let Some(read) = &mut self.read {
+                read.incr_data = Some(Vec::new());
+                read.last_activity = Instant::now();
+            }
+
+            return Ok(None);
+        }
+
+        Ok(self.finish_read(xcb_connection, Some((reply.type_, reply.value))))
+    }
+
+    /// Append the next chunk of an `INCR` transfer from the selection owner.
+    fn continue_read(
+        &mut self, xcb_connection: &XcbConnection, event: &PropertyNotifyEvent,
+    ) -> Result<Option<ClipboardEvent>, Box<dyn Error>> {
+        if event.state != Property::NEW_VALUE
+            || event.atom != xcb_connection.atoms.BASEVIEW_SELECTION
+        {
+            return Ok(None);
+        }
+
+        let (data, last_activity) = match &mut self.read {
+            Some(SelectionRead { incr_data: Some(data), last_activity, .. }) => {
+                (data, last_activity)
+            }
+            _ => return Ok(None),
+        };
+
+        let reply = xcb_connection
+            .conn
+            .get_property(true, self.window, event.atom, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        // Once again, an empty chunk marks the end of the transfer
+        if !reply.value.is_empty() {
+            data.extend_from_slice(&reply.value);
+            *last_activity = Instant::now();
+
+            return Ok(None);
+        }
+
+        let data = std::mem::take(data);
+        Ok(self.finish_read(xcb_connection, Some((reply.type_, data))))
+    }
+
+    /// Whether we're waiting on the selection owner to convert a selection for us.
+    pub fn is_reading(&self) -> bool {
+        self.read.is_some()
+    }
+
+    /// Give up on the current conversion if the selection owner has stopped responding.
+    pub fn expire_stale_read(&mut self, xcb_connection: &XcbConnection) -> Option<ClipboardEvent> {
+        match &self.read {
+            Some(read) if read.last_activity.elapsed() >= READ_TIMEOUT => {
+                self.finish_read(xcb_connection, None)
+            }
+            _ => None,
+        }
+    }
+
+    /// Finish the current conversion with the data received for it, and move on to the next one.
+    /// `None` means the conversion failed.
+    fn finish_read(
+        &mut self, xcb_connection: &XcbConnection, data: Option<(Atom, Vec<u8>)>,
+    ) -> Option<ClipboardEvent> {
+        let request = self.read.take()?.request;
+        let event = read_event(xcb_connection, request, data);
+
+        self.start_next_read(xcb_connection);
+
+        Some(event)
+    }
+}
+
+/// Turn the data received for a conversion, along with the type of the property it was received
+/// through, into an event for the handler. `None` results in an empty response.
+fn read_event(
+    xcb_connection: &XcbConnection, request: ReadRequest, data: Option<(Atom, Vec<u8>)>,
+) -> ClipboardEvent {
+    match (request, data) {
+        (ReadRequest::Text, data) => {
+            ClipboardEvent::Text(data.map(|(data_type, data)| decode_text(data_type, &data)))
+        }
+        (ReadRequest::Data(format), data) => {
+            ClipboardEvent::Data(data.map(|(data_type, data)| match format {
+                ClipboardFormat::Text => ClipboardData::Text(decode_text(data_type, &data)),
+                ClipboardFormat::UriList => ClipboardData::UriList(decode_uri_list(&data)),
+                ClipboardFormat::Png => ClipboardData::Png(data),
+                ClipboardFormat::Custom(mime) => ClipboardData::Custom { mime, data },
+            }))
+        }
+        (ReadRequest::Formats, Some((_, data))) => {
+            ClipboardEvent::Formats(decode_formats(xcb_connection, &data))
+        }
+        (ReadRequest::Formats, None) => ClipboardEvent::Formats(Vec::new()),
+        (ReadRequest::PrimaryText, data) => {
+            ClipboardEvent::PrimaryText(data.map(|(data_type, data)| decode_text(data_type, &data)))
+        }
+    }
+}
+
+/// Add the targets `data` can be converted to when we own the selection.
+fn add_representations(
+    xcb_connection: &XcbConnection, representations: &mut Vec<Representation>, data: ClipboardData,
+) -> Result<(), Box<dyn Error>> {
+    let atoms = &xcb_connection.atoms;
+
+    match data {
+        ClipboardData::Text(text) => {
+            let string: Rc<[u8]> = latin1_from_utf8(text.as_bytes()).into();
+            let utf8: Rc<[u8]> = text.into_bytes().into();
+
+            for target in [atoms.UTF8_STRING, atoms.TEXT_PLAIN_UTF8] {
+                representations.push(Representation {
+                    target,
+                    data_type: target,
+                    data: Rc::clone(&utf8),
+                });
+            }
+            representations.push(Representation {
+                target: atoms.TEXT,
+                data_type: atoms.UTF8_STRING,
+                data: utf8,
+            });
+            representations.push(Representation {
+                target: AtomEnum::STRING.into(),
+                data_type: AtomEnum::STRING.into(),
+                data: string,
+            });
+        }
+        ClipboardData::UriList(uris) => {
+            // URI lists use CRLF line endings
+            let data: String = uris.iter().map(|uri| format!("{}\r\n", uri)).collect();
+            representations.push(Representation {
+                target: atoms.URI_LIST,
+                data_type: atoms.URI_LIST,
+                data: data.into_bytes().into(),
+            });
+        }
+        ClipboardData::Png(data) => {
+            representations.push(Representation {
+                target: atoms.IMAGE_PNG,
+                data_type: atoms.IMAGE_PNG,
+                data: data.into(),
+            });
+        }
+        ClipboardData::Custom { mime, data } => {
+            let target = xcb_connection.intern_atom(&mime)?;
+            representations.push(Representation { target, data_type: target, data: data.into() });
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode text received through a selection based on the property's type.
+pub(super) fn decode_text(data_type: Atom, data: &[u8]) -> String {
+    if data_type == AtomEnum::STRING.into() {
+        data.iter().map(|&c| c as char).collect()
+    } else {
+        String::from_utf8_lossy(data).into_owned()
+    }
+}
+
+/// Parse a `text/uri-list`, skipping over any comments.
+pub(super) fn decode_uri_list(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Map the atoms from a `TARGETS` conversion to the formats we know how to request. Targets that
+/// don't look like MIME types, like `TIMESTAMP` or `COMPOUND_TEXT`, are skipped.
+fn decode_formats(xcb_connection: &XcbConnection, data: &[u8]) -> Vec<ClipboardFormat> {
+    let atoms = &xcb_connection.atoms;
+    let text_targets =
+        [atoms.UTF8_STRING, atoms.TEXT_PLAIN_UTF8, atoms.TEXT, AtomEnum::STRING.into()];
+
+    let mut formats = Vec::new();
+    for target in data.chunks_exact(4).map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]])) {
+        let format = if text_targets.contains(&target) {
+            ClipboardFormat::Text
+        } else if target == atoms.URI_LIST {
+            ClipboardFormat::UriList
+        } else if target == atoms.IMAGE_PNG {
+            ClipboardFormat::Png
+        } else {
+            // Targets we can't look up are skipped like any other target we don't understand
+            match xcb_connection.atom_name(target) {
+                Ok(name) if name.contains('/') => ClipboardFormat::Custom(name),
+                _ => continue,
+            }
+        };
+
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+
+    formats
+}
+
+/// The largest amount of property data we'll send in a single request.
+fn max_chunk_size(xcb_connection: &XcbConnection) -> usize {
+    // Leave some headroom for the `ChangeProperty` request's header
+    (xcb_connection.conn.maximum_request_bytes() / 4).min(1 << 20)
+}
+
+/// `STRING` is defined to be ISO Latin-1, so anything outside of that range gets replaced.
+pub(super) fn latin1_from_utf8(data: &[u8]) -> Vec<u8> {
+    String::from_utf8_lossy(data)
+        .chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect()
+}
@@ -0,0 +1,426 @@
+use std::cell::RefCell;
+use std::os::fd::AsRawFd;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::Window as XWindow;
+use x11rb::protocol::Event as XEvent;
+
+use super::event_loop::EventLoop;
+use super::waker::Waker;
+use super::XcbConnection;
+use crate::Error;
+
+/// A closure sent to the dispatcher thread from another thread.
+type Request = Box<dyn FnOnce(&Dispatcher) + Send>;
+
+/// The sending end of the running dispatcher thread's request queue, if there is one.
+static DISPATCHER: Lazy<Mutex<Option<DispatcherHandle>>> = Lazy::new(|| Mutex::new(None));
+
+thread_local! {
+    /// Set on the dispatcher thread, so requests made from the window handlers running on it can
+    /// be handled right away instead of being queued.
+    static CURRENT: RefCell<Option<Rc<Dispatcher>>> = const { RefCell::new(None) };
+}
+
+struct DispatcherHandle {
+    requests: mpsc::Sender<Request>,
+    waker: Arc<Waker>,
+}
+
+/// All windows in the process share a single X11 connection and a single thread. The dispatcher
+/// running on that thread reads the connection's events and routes them to the windows they're
+/// meant for, and keeps track of when each window wants to draw its next frame. The thread is
+/// started when the first window gets opened, and exits again once the last window has closed.
+///
+/// Embedded windows instead get a dispatcher of their own, which is driven by the host's event
+/// loop. See [`EmbeddedWindow`][super::EmbeddedWindow].
+pub(super) struct Dispatcher {
+    connection: Rc<XcbConnection>,
+    windows: RefCell<Vec<(XWindow, Rc<RefCell<EventLoop>>)>>,
+}
+
+/// Run `request` on the dispatcher thread and wait for its result, starting the thread if it isn't
+/// already running. When called from the dispatcher thread itself, for instance from a window's
+/// handler, `request` is run directly. Fails if the thread could not connect to the X server.
+pub(super) fn run<R, F>(request: F) -> Result<R, Error>
+where
+    R: Send + 'static,
+    F: FnOnce(&Dispatcher) -> R + Send + 'static,
+{
+    if let Some(dispatcher) = CURRENT.with(|current| current.borrow().clone()) {
+        return Ok(request(&dispatcher));
+    }
+
+    let (tx, rx) = mpsc::sync_channel(1);
+    let mut request: Request = Box::new(move |dispatcher| {
+        let _ = tx.send(request(dispatcher));
+    });
+
+    {
+        let mut handle = DISPATCHER.lock().unwrap();
+        loop {
+            if handle.is_none() {
+                *handle = Some(spawn()?);
+            }
+
+            // Sending only fails if the dispatcher thread has died, in which case we'll start a new
+            // one
+            let dispatcher = handle.as_ref().unwrap();
+            match dispatcher.requests.send(request) {
+                Ok(()) => {
+                    dispatcher.waker.wake();
+                    break;
+                }
+                Err(mpsc::SendError(unsent)) => {
+                    request = unsent;
+                    *handle = None;
+                }
+            }
+        }
+    }
+
+    // The request only gets dropped without being run if the dispatcher thread has died
+    rx.recv().map_err(|_| thread_exited())
+}
+
+/// Whether this is the thread the windows' handlers run on.
+pub(super) fn is_dispatcher_thread() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+/// Start the dispatcher thread, and wait for it to connect to the X server.
+fn spawn() -> Result<DispatcherHandle, Error> {
+    let (requests_tx, requests_rx) = mpsc::channel();
+    let waker = Waker::new().map_err(|err| Error::ConnectionFailed(err.to_string()))?;
+    let waker = Arc::new(waker);
+    let (connected_tx, connected_rx) = mpsc::sync_channel(1);
+
+    let thread_waker = Arc::clone(&waker);
+    thread::spawn(move || {
+        let dispatcher = match Dispatcher::new() {
+            Ok(dispatcher) => Rc::new(dispatcher),
+            Err(err) => {
+                let _ = connected_tx.send(Err(err));
+                return;
+            }
+        };
+        let _ = connected_tx.send(Ok(()));
+
+        CURRENT.with(|current| *current.borrow_mut() = Some(Rc::clone(&dispatcher)));
+        dispatcher.run(&requests_rx, &thread_waker);
+        CURRENT.with(|current| *current.borrow_mut() = None);
+    });
+
+    connected_rx.recv().unwrap_or_else(|_| Err(thread_exited()))?;
+
+    Ok(DispatcherHandle { requests: requests_tx, waker })
+}
+
+fn thread_exited() -> Error {
+    Error::ConnectionFailed("The X11 event loop thread has exited".into())
+}
+
+impl Dispatcher {
+    pub fn new() -> Result<Self, Error> {
+        let connection =
+            XcbConnection::new().map_err(|err| Error::ConnectionFailed(err.to_string()))?;
+
+        Ok(Self { connection: Rc::new(connection), windows: RefCell::new(Vec::new()) })
+    }
+
+    pub fn connection(&self) -> &Rc<XcbConnection> {
+        &self.connection
+    }
+
+    /// Start dispatching events to a newly created window.
+    pub fn add_window(&self, event_loop: EventLoop) {
+        let window_id = event_loop.window_id();
+
+        self.connection.windows.borrow_mut().insert(window_id);
+        self.windows.borrow_mut().push((window_id, Rc::new(RefCell::new(event_loop))));
+    }
+
+    // FIXME: poll() acts fine on linux, sometimes funky on *BSD. XCB upstream uses a define to
+    // switch between poll() and select() (the latter of which is fine on *BSD), and we should do
+    // the same.
+    fn run(&self, requests: &mpsc::Receiver<Request>, waker: &Waker) {
+        use nix::errno::Errno;
+        use nix::poll::*;
+
+        let xcb_fd = self.connection.conn.as_raw_fd();
+
+        loop {
+            while let Ok(request) = requests.try_recv() {
+                request(self);
+            }
+
+            self.dispatch();
+            if !self.has_windows() && self.try_exit(requests) {
+                return;
+            }
+
+            let windows = self.windows();
+            let mut fds = vec![
+                PollFd::new(xcb_fd, PollFlags::POLLIN),
+                PollFd::new(waker.fd(), PollFlags::POLLIN),
+            ];
+            fds.extend(
+                windows
+                    .iter()
+                    .map(|window| PollFd::new(window.borrow().waker_fd(), PollFlags::POLLIN)),
+            );
+
+            // With nothing to draw and nothing to time out, we can sleep until the X server sends
+            // us an event or one of the wakers gets woken up
+            let timeout = match self.poll_timeout() {
+                Some(timeout) => {
+                    timeout.min(Duration::from_millis(i32::MAX as u64)).as_millis() as i32
+                }
+                None => -1,
+            };
+
+            match poll(&mut fds, timeout) {
+                Ok(_) => {}
+                // A signal handler ran while we were waiting, so we'll just wait again
+                Err(Errno::EINTR) => continue,
+                Err(_) => {
+                    self.shut_down();
+                    return;
+                }
+            }
+
+            // The X server has gone away, so there's nothing left for any of the windows to do
+            if let Some(revents) = fds[0].revents() {
+                if revents.intersects(PollFlags::POLLERR | PollFlags::POLLHUP) {
+                    self.shut_down();
+                    return;
+                }
+            }
+
+            if let Some(revents) = fds[1].revents() {
+                if revents.contains(PollFlags::POLLIN) {
+                    waker.reset();
+                }
+            }
+
+            // The messages themselves are handled in `EventLoop::update()`
+            for (fd, window) in fds[2..].iter().zip(&windows) {
+                if fd.revents().map_or(false, |revents| revents.contains(PollFlags::POLLIN)) {
+                    window.borrow().reset_waker();
+                }
+            }
+        }
+    }
+
+    /// Draw the frames that are due, handle all pending events, and drop the windows that have
+    /// closed. This never blocks. Both the dispatcher thread and embedded windows call this before
+    /// going back to waiting on the connection's file descriptor.
+    pub fn dispatch(&self) {
+        for window in self.windows() {
+            window.borrow_mut().draw_frame_if_due();
+        }
+
+        // Handling events and updating the windows can involve round trips to the X server. Events
+        // that arrive while we wait for those replies end up in xcb's internal queue without making
+        // the file descriptor readable, so we need to keep going until that queue is empty or we
+        // would go to sleep with those events still unhandled.
+        let mut queued_event = None;
+        loop {
+            if let Some(event) = queued_event.take() {
+                self.dispatch_xcb_event(event);
+            }
+            self.drain_xcb_events();
+
+            for window in self.windows() {
+                window.borrow_mut().update();
+            }
+
+            match self.connection.conn.poll_for_event() {
+                Ok(Some(event)) => queued_event = Some(event),
+                _ => break,
+            }
+        }
+
+        self.remove_closed_windows();
+    }
+
+    /// How long we can wait before [`dispatch()`][Self::dispatch()] needs to be called again when
+    /// no events come in. The windows each tell us how long they can wait for their next frame or
+    /// timeout. `None` means there's nothing to wait for.
+    pub fn poll_timeout(&self) -> Option<Duration> {
+        self.windows().iter().filter_map(|window| window.borrow().poll_timeout()).min()
+    }
+
+    pub fn has_windows(&self) -> bool {
+        !self.windows.borrow().is_empty()
+    }
+
+    /// Close all windows right away, sending their handlers a `WillClose` event. Nothing will
+    /// dispatch events to the windows after this, so this blocks until clipboard managers have
+    /// taken over the windows' clipboards.
+    pub fn close_windows(&self) {
+        use nix::errno::Errno;
+        use nix::poll::*;
+
+        for window in self.windows() {
+            window.borrow_mut().close();
+        }
+
+        let xcb_fd = self.connection.conn.as_raw_fd();
+        loop {
+            self.dispatch();
+
+            let timeout = match self.poll_timeout() {
+                Some(timeout) if self.has_windows() => timeout,
+                _ => break,
+            };
+
+            let mut fds = [PollFd::new(xcb_fd, PollFlags::POLLIN)];
+            match poll(&mut fds, timeout.as_millis() as i32) {
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(_) => break,
+            }
+
+            let revents = fds[0].revents().unwrap_or_else(PollFlags::empty);
+            if revents.intersects(PollFlags::POLLERR | PollFlags::POLLHUP) {
+                break;
+            }
+        }
+
+        self.drop_windows();
+    }
+
+    /// A snapshot of the open windows. Handlers may open and close windows while we're iterating
+    /// over these.
+    fn windows(&self) -> Vec<Rc<RefCell<EventLoop>>> {
+        self.windows.borrow().iter().map(|(_, window)| Rc::clone(window)).collect()
+    }
+
+    fn drain_xcb_events(&self) {
+        while let Ok(Some(event)) = self.connection.conn.poll_for_event() {
+            self.dispatch_xcb_event(event);
+        }
+
+        // the X server has a tendency to send spurious/extraneous configure notify events when a
+        // window is resized, and we need to batch those together and just send one resize event
+        // when they've all been coalesced.
+        for window in self.windows() {
+            window.borrow_mut().flush_configure();
+        }
+    }
+
+    fn dispatch_xcb_event(&self, event: XEvent) {
+        // Property changes may be relevant to any window, since clipboard transfers listen for
+        // them on the requestor's window
+        if let XEvent::PropertyNotify(_) = event {
+            for window in self.windows() {
+                window.borrow_mut().handle_xcb_event(event.clone());
+            }
+
+            return;
+        }
+
+        let window_id = match event_window(&event) {
+            Some(window_id) => window_id,
+            None => return,
+        };
+
+        let window = self
+            .windows
+            .borrow()
+            .iter()
+            .find(|(id, _)| *id == window_id)
+            .map(|(_, window)| Rc::clone(window));
+        if let Some(window) = window {
+            window.borrow_mut().handle_xcb_event(event);
+        }
+    }
+
+    fn remove_closed_windows(&self) {
+        // The windows get destroyed when their event loops are dropped, which needs to happen
+        // outside of the borrow
+        let mut closed = Vec::new();
+        self.windows.borrow_mut().retain(|(window_id, window)| {
+            let is_closed = window.borrow().is_closed();
+            if is_closed {
+                closed.push(Rc::clone(window));
+                self.connection.windows.borrow_mut().remove(window_id);
+            }
+
+            !is_closed
+        });
+
+        drop(closed);
+    }
+
+    /// Drop all windows, including the ones that are still waiting on a clipboard manager.
+    fn drop_windows(&self) {
+        let windows = std::mem::take(&mut *self.windows.borrow_mut());
+        self.connection.windows.borrow_mut().clear();
+
+        drop(windows);
+    }
+
+    /// Close all windows after the connection has broken, and stop taking requests so the next
+    /// window that gets opened starts a new dispatcher thread. Requests that were already queued
+    /// get dropped along with the queue, which makes them fail with an error.
+    fn shut_down(&self) {
+        // There's no point in waiting for a clipboard manager anymore
+        for window in self.windows() {
+            window.borrow_mut().close();
+        }
+        self.drop_windows();
+
+        *DISPATCHER.lock().unwrap() = None;
+    }
+
+    /// Called when the last window has closed. Returns `false` if another window is about to be
+    /// opened, and `true` if the thread can exit.
+    fn try_exit(&self, requests: &mpsc::Receiver<Request>) -> bool {
+        // New requests can only be sent while holding this lock, so if the queue is empty now, it
+        // will stay that way
+        let mut handle = DISPATCHER.lock().unwrap();
+        match requests.try_recv() {
+            Ok(request) => {
+                drop(handle);
+                request(self);
+
+                false
+            }
+            Err(_) => {
+                *handle = None;
+
+                true
+            }
+        }
+    }
+}
+
+/// The window an event is meant for, if it is meant for a specific window.
+fn event_window(event: &XEvent) -> Option<XWindow> {
+    let window = match event {
+        XEvent::KeyPress(event) | XEvent::KeyRelease(event) => event.event,
+        XEvent::ButtonPress(event) | XEvent::ButtonRelease(event) => event.event,
+        XEvent::MotionNotify(event) => event.event,
+        XEvent::EnterNotify(event) | XEvent::LeaveNotify(event) => event.event,
+        XEvent::FocusIn(event) | XEvent::FocusOut(event) => event.event,
+        XEvent::Expose(event) => event.window,
+        XEvent::ConfigureNotify(event) => event.window,
+        XEvent::DestroyNotify(event) => event.window,
+        XEvent::ClientMessage(event) => event.window,
+        XEvent::SelectionRequest(event) => event.owner,
+        XEvent::SelectionClear(event) => event.owner,
+        XEvent::SelectionNotify(event) => event.requestor,
+        XEvent::XfixesSelectionNotify(event) => event.window,
+        XEvent::PresentCompleteNotify(event) => event.window,
+        _ => return None,
+    };
+
+    Some(window)
+}
@@ -0,0 +1,1052 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use keyboard_types::Modifiers;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, GrabMode, GrabStatus,
+    PropMode, Property, PropertyNotifyEvent, SelectionNotifyEvent, Timestamp, Window as XWindow,
+};
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{CURRENT_TIME, NONE};
+
+use super::clipboard::{decode_text, decode_uri_list, Clipboard, Selection};
+use super::keyboard::key_mods;
+use super::XcbConnection;
+use crate::{
+    ClipboardData, DragData, DropData, DropEffect, EventStatus, MouseCursor, MouseEvent, PhyPoint,
+    Point, WindowInfo,
+};
+
+/// The version of the XDND protocol we implement.
+pub(super) const XDND_VERSION: u32 = 5;
+
+/// The oldest version of the XDND protocol we'll drag to.
+const MIN_XDND_VERSION: u32 = 3;
+
+/// How long we'll wait on the source to send the dragged data before giving up on it.
+const DATA_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long we'll wait for the drop target to finish a drop before giving up on it.
+const DROP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A drag from another client that's currently hovering over our window.
+struct DragSession {
+    source: XWindow,
+    /// The protocol version we agreed on with the source.
+    version: u32,
+    types: Vec<Atom>,
+
+    // These are cached since the drop and leave messages don't include them
+    position: Point,
+    modifiers: Modifiers,
+    /// The dragged data, or `None` if we're still waiting on the source to convert it for us.
+    data: Option<DropData>,
+    /// The conversion of the dragged data we're currently waiting on, if any.
+    request: Option<DataRequest>,
+    /// Whether the data has been dropped and we're waiting on its payload before we can tell the
+    /// handler about it.
+    dropped: bool,
+    /// Whether the handler has received a `DragEntered` event for this drag.
+    entered: bool,
+    /// The action we accepted in our last `XdndStatus` reply, if any.
+    accepted_action: Option<Atom>,
+}
+
+/// A conversion of the dragged data we've asked the source for.
+struct DataRequest {
+    target: Atom,
+    /// The data received so far, if the source is sending it to us through an `INCR` transfer.
+    incr_data: Option<Vec<u8>>,
+    last_activity: Instant,
+}
+
+/// What we still need to tell the source once the handler has decided what to do with a drag
+/// event.
+enum PendingReply {
+    Status,
+    Finished,
+}
+
+/// The receiving end of the XDND protocol. Other clients send us client messages while they drag
+/// something over our window, and we reply with whether we'd accept a drop based on what the
+/// handler returns for the corresponding [`MouseEvent`].
+///
+/// File lists and text are fetched as soon as the drag enters the window so the handler can decide
+/// whether to accept them. Other MIME types may be large, so their payloads are only fetched once
+/// they're dropped.
+pub(crate) struct DropTarget {
+    window: XWindow,
+    session: Option<DragSession>,
+    pending_reply: Option<PendingReply>,
+}
+
+impl DropTarget {
+    pub fn new(window: XWindow) -> Self {
+        Self { window, session: None, pending_reply: None }
+    }
+
+    /// Handle an XDND client message. Returns an event for the handler, after which
+    /// [`respond()`][Self::respond()] should be called with the handler's response.
+    pub fn handle_client_message(
+        &mut self, xcb_connection: &XcbConnection, window_info: &WindowInfo,
+        event: &ClientMessageEvent,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        let atoms = &xcb_connection.atoms;
+        if event.format != 32 {
+            return Ok(None);
+        }
+
+        let data = event.data.as_data32();
+        if event.type_ == atoms.XdndEnter {
+            self.handle_enter(xcb_connection, data)?;
+            Ok(None)
+        } else if event.type_ == atoms.XdndPosition {
+            self.handle_position(xcb_connection, window_info, data)
+        } else if event.type_ == atoms.XdndDrop {
+            self.handle_drop(xcb_connection, data)
+        } else if event.type_ == atoms.XdndLeave {
+            Ok(self.handle_leave(data))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn handle_enter(
+        &mut self, xcb_connection: &XcbConnection, data: [u32; 5],
+    ) -> Result<(), Box<dyn Error>> {
+        let source = data[0];
+        let version = data[1] >> 24;
+        if version > XDND_VERSION {
+            self.session = None;
+            return Ok(());
+        }
+
+        // The first three types are sent along with the message, and any additional types are
+        // stored in a property on the source window
+        let types = if data[1] & 1 != 0 {
+            xcb_connection
+                .conn
+                .get_property(
+                    false,
+                    source,
+                    xcb_connection.atoms.XdndTypeList,
+                    AtomEnum::ATOM,
+                    0,
+                    u32::MAX,
+                )?
+                .reply()?
+                .value32()
+                .map(|types| types.collect())
+                .unwrap_or_default()
+        } else {
+            data[2..].iter().copied().filter(|&atom| atom != NONE).collect()
+        };
+
+        self.session = Some(DragSession {
+            source,
+            version,
+            types,
+            position: Point::new(0.0, 0.0),
+            modifiers: Modifiers::empty(),
+            data: None,
+            request: None,
+            dropped: false,
+            entered: false,
+            accepted_action: None,
+        });
+        self.pending_reply = None;
+
+        Ok(())
+    }
+
+    fn handle_position(
+        &mut self, xcb_connection: &XcbConnection, window_info: &WindowInfo, data: [u32; 5],
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        let session = match &mut self.session {
+            Some(session) if session.source == data[0] && !session.dropped => session,
+            _ => return Ok(None),
+        };
+
+        // The position is sent in root window coordinates
+        let conn = &xcb_connection.conn;
+        let root = xcb_connection.screen().root;
+        let (root_x, root_y) = ((data[2] >> 16) as i16, data[2] as i16);
+        let translated = conn.translate_coordinates(root, self.window, root_x, root_y)?.reply()?;
+        let physical_pos = PhyPoint::new(translated.dst_x as i32, translated.dst_y as i32);
+        session.position = physical_pos.to_logical(window_info);
+
+        // XDND doesn't tell us which modifiers are held down, so we'll need to ask for them
+        session.modifiers = key_mods(conn.query_pointer(self.window)?.reply()?.mask);
+
+        if session.data.is_none() {
+            if session.request.is_some() {
+                return Ok(None);
+            }
+
+            let atoms = &xcb_connection.atoms;
+            let text_target = [atoms.UTF8_STRING, atoms.TEXT_PLAIN_UTF8, AtomEnum::STRING.into()]
+                .iter()
+                .copied()
+                .find(|target| session.types.contains(target));
+            let eager_target = if session.types.contains(&atoms.URI_LIST) {
+                Some(atoms.URI_LIST)
+            } else {
+                text_target
+            };
+
+            match eager_target {
+                Some(target) => {
+                    // The source may only be asked for its data while it's hovering over our
+                    // window. We'll reply to this message once the data has arrived.
+                    let timestamp: Timestamp = data[3];
+                    request_data(xcb_connection, self.window, session, target, timestamp)?;
+
+                    return Ok(None);
+                }
+                None => session.data = Some(mime_data(xcb_connection, &session.types)?),
+            }
+        }
+
+        Ok(self.drag_event())
+    }
+
+    /// Receive the dragged data we asked the source for.
+    pub fn handle_selection_notify(
+        &mut self, xcb_connection: &XcbConnection, event: &SelectionNotifyEvent,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        let session = match &mut self.session {
+            Some(session) if event.requestor == self.window => session,
+            _ => return Ok(None),
+        };
+        let request = match &mut session.request {
+            Some(request) if request.target == event.target => request,
+            _ => return Ok(None),
+        };
+
+        if event.property == NONE {
+            return Ok(self.finish_request(xcb_connection, None));
+        }
+
+        let reply = xcb_connection
+            .conn
+            .get_property(true, self.window, event.property, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        if reply.type_ == xcb_connection.atoms.INCR {
+            // Deleting the property (which `get_property()` just did) tells the source to start
+            // sending chunks, which we'll receive through `PropertyNotify` events
+            request.incr_data = Some(Vec::new());
+            request.last_activity = Instant::now();
+
+            return Ok(None);
+        }
+
+        Ok(self.finish_request(xcb_connection, Some((reply.type_, reply.value))))
+    }
+
+    /// Append the next chunk of an `INCR` transfer from the source.
+    pub fn handle_property_notify(
+        &mut self, xcb_connection: &XcbConnection, event: &PropertyNotifyEvent,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        if event.window != self.window
+            || event.state != Property::NEW_VALUE
+            || event.atom != xcb_connection.atoms.BASEVIEW_DRAG_DATA
+        {
+            return Ok(None);
+        }
+
+        let (data, last_activity) = match &mut self.session {
+            Some(DragSession {
+                request: Some(DataRequest { incr_data: Some(data), last_activity, .. }),
+                ..
+            }) => (data, last_activity),
+            _ => return Ok(None),
+        };
+
+        let reply = xcb_connection
+            .conn
+            .get_property(true, self.window, event.atom, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        // An empty chunk marks the end of the transfer
+        if !reply.value.is_empty() {
+            data.extend_from_slice(&reply.value);
+            *last_activity = Instant::now();
+
+            return Ok(None);
+        }
+
+        let data = std::mem::take(data);
+        Ok(self.finish_request(xcb_connection, Some((reply.type_, data))))
+    }
+
+    /// Whether we're waiting on the source to convert the dragged data for us.
+    pub fn is_fetching_data(&self) -> bool {
+        matches!(self.session, Some(DragSession { request: Some(_), .. }))
+    }
+
+    /// Give up on the dragged data if the source has stopped responding.
+    pub fn expire_stale_request(&mut self, xcb_connection: &XcbConnection) -> Option<MouseEvent> {
+        match &self.session {
+            Some(DragSession { request: Some(request), .. })
+                if request.last_activity.elapsed() >= DATA_TIMEOUT =>
+            {
+                self.finish_request(xcb_connection, None)
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode the data received for the current conversion, along with the type of the property it
+    /// was received through. Returns the event that was waiting on the data.
+    fn finish_request(
+        &mut self, xcb_connection: &XcbConnection, data: Option<(Atom, Vec<u8>)>,
+    ) -> Option<MouseEvent> {
+        let session = self.session.as_mut()?;
+        let request = session.request.take()?;
+
+        let atoms = &xcb_connection.atoms;
+        let decoded = match (session.data.take(), data) {
+            // This was the payload for a MIME type, which we only fetch after the drop
+            (Some(DropData::Mime { mime, .. }), data) => {
+                DropData::Mime { mime, data: data.map(|(_, data)| data) }
+            }
+            (_, None) => DropData::None,
+            (_, Some((_, data))) if request.target == atoms.URI_LIST => {
+                let uris = decode_uri_list(&data);
+                let hostname = local_hostname();
+                let paths: Option<Vec<PathBuf>> =
+                    uris.iter().map(|uri| path_from_uri(uri, hostname.as_deref())).collect();
+                match paths {
+                    _ if uris.is_empty() => DropData::None,
+                    Some(paths) => DropData::Files(paths),
+                    None => DropData::Urls(uris),
+                }
+            }
+            (_, Some((data_type, data))) => DropData::Text(decode_text(data_type, &data)),
+        };
+        session.data = Some(decoded);
+
+        if session.dropped {
+            Some(self.drop_event())
+        } else {
+            self.drag_event()
+        }
+    }
+
+    /// Build the `DragEntered` or `DragMoved` event for the current position of the drag.
+    fn drag_event(&mut self) -> Option<MouseEvent> {
+        let session = self.session.as_mut()?;
+        let data = session.data.clone()?;
+
+        self.pending_reply = Some(PendingReply::Status);
+        if session.entered {
+            Some(MouseEvent::DragMoved {
+                position: session.position,
+                modifiers: session.modifiers,
+                data,
+            })
+        } else {
+            session.entered = true;
+            Some(MouseEvent::DragEntered {
+                position: session.position,
+                modifiers: session.modifiers,
+                data,
+            })
+        }
+    }
+
+    fn drop_event(&mut self) -> MouseEvent {
+        self.pending_reply = Some(PendingReply::Finished);
+
+        // Only called while there's a session with data
+        let session = self.session.as_ref().unwrap();
+        MouseEvent::DragDropped {
+            position: session.position,
+            modifiers: session.modifiers,
+            data: session.data.clone().unwrap_or(DropData::None),
+        }
+    }
+
+    fn handle_drop(
+        &mut self, xcb_connection: &XcbConnection, data: [u32; 5],
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        let session = match &mut self.session {
+            Some(session) if session.source == data[0] && !session.dropped => session,
+            _ => return Ok(None),
+        };
+
+        // The handler never accepted the drop, so as far as it's concerned the drag simply left
+        // the window
+        if session.data.is_none() || session.accepted_action.is_none() {
+            let entered = session.entered;
+            self.send_finished(xcb_connection, None)?;
+            self.session = None;
+
+            return Ok(if entered { Some(MouseEvent::DragLeft) } else { None });
+        }
+
+        session.dropped = true;
+        if let Some(DropData::Mime { mime, data: None }) = &session.data {
+            // Now that the data has been dropped we can fetch the actual payload. The source
+            // will wait for our `XdndFinished` message in the meantime.
+            let target = xcb_connection.intern_atom(mime)?;
+            let timestamp: Timestamp = data[2];
+            request_data(xcb_connection, self.window, session, target, timestamp)?;
+
+            return Ok(None);
+        }
+
+        Ok(Some(self.drop_event()))
+    }
+
+    fn handle_leave(&mut self, data: [u32; 5]) -> Option<MouseEvent> {
+        match &self.session {
+            Some(session) if session.source == data[0] => {
+                let entered = session.entered;
+                self.session = None;
+                self.pending_reply = None;
+
+                if entered {
+                    Some(MouseEvent::DragLeft)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Tell the source whether the handler accepts the drag, based on what it returned for the
+    /// last event from [`handle_client_message()`][Self::handle_client_message()] or
+    /// [`handle_selection_notify()`][Self::handle_selection_notify()].
+    pub fn respond(
+        &mut self, xcb_connection: &XcbConnection, status: EventStatus,
+    ) -> Result<(), Box<dyn Error>> {
+        let atoms = &xcb_connection.atoms;
+        let action = match status {
+            EventStatus::AcceptDrop(DropEffect::Copy) => Some(atoms.XdndActionCopy),
+            EventStatus::AcceptDrop(DropEffect::Move) => Some(atoms.XdndActionMove),
+            EventStatus::AcceptDrop(DropEffect::Link) => Some(atoms.XdndActionLink),
+            // XDND has no notion of scrolling the target, so this just means that the handler
+            // doesn't want the drop (yet)
+            _ => None,
+        };
+
+        match self.pending_reply.take() {
+            Some(PendingReply::Status) => {
+                let session = match &mut self.session {
+                    Some(session) => session,
+                    None => return Ok(()),
+                };
+                session.accepted_action = action;
+
+                // We always ask for new position messages, even when the mouse stays within the
+                // same rectangle, since the handler gets to decide for every position
+                let flags = if action.is_some() { 0b11 } else { 0b10 };
+                send_client_message(
+                    xcb_connection,
+                    session.source,
+                    atoms.XdndStatus,
+                    [self.window, flags, 0, 0, action.unwrap_or(NONE)],
+                )?;
+            }
+            Some(PendingReply::Finished) => {
+                self.send_finished(xcb_connection, action)?;
+                self.session = None;
+            }
+            None => (),
+        }
+
+        Ok(())
+    }
+
+    fn send_finished(
+        &self, xcb_connection: &XcbConnection, action: Option<Atom>,
+    ) -> Result<(), Box<dyn Error>> {
+        let session = match &self.session {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+
+        // Older versions of the protocol don't include whether the drop was successful
+        let data = if session.version >= 5 {
+            [self.window, action.is_some() as u32, action.unwrap_or(NONE), 0, 0]
+        } else {
+            [self.window, 0, 0, 0, 0]
+        };
+
+        send_client_message(xcb_connection, session.source, xcb_connection.atoms.XdndFinished, data)
+    }
+}
+
+/// A drop target we're currently dragging over.
+struct DragTarget {
+    window: XWindow,
+    /// The protocol version we agreed on with the target.
+    version: u32,
+    /// Whether we're still waiting on an `XdndStatus` reply to the last position we sent. New
+    /// positions may only be sent after the target has replied.
+    awaiting_status: bool,
+    /// The action the target accepted in its last `XdndStatus` reply, if any.
+    accepted_action: Option<Atom>,
+}
+
+/// The sending end of the XDND protocol, for drags started with
+/// [`Window::start_drag()`][crate::Window::start_drag()]. While dragging we grab the pointer, and we
+/// send client messages to whichever XDND aware window is under it. The data itself is served
+/// through the `XdndSelection` selection by [`Clipboard`].
+pub(crate) struct DragSource {
+    window: XWindow,
+    /// Whether the pointer is grabbed and its movement is steering the drag.
+    dragging: bool,
+    types: Vec<Atom>,
+    /// The actions the target may perform, in order of preference.
+    actions: Vec<Atom>,
+    target: Option<DragTarget>,
+    /// The latest pointer position in root window coordinates, if it still needs to be sent to the
+    /// target once it has replied to the previous one.
+    pending_position: Option<(i16, i16, Timestamp)>,
+    /// When we dropped the data onto the target, if we're waiting for it to finish the drop.
+    dropped_at: Option<Instant>,
+    /// The outcome of a drag that ended without a corresponding X11 event, like when the pointer
+    /// couldn't be grabbed.
+    outcome: Option<Option<DropEffect>>,
+}
+
+impl DragSource {
+    pub fn new(window: XWindow) -> Self {
+        Self {
+            window,
+            dragging: false,
+            types: Vec::new(),
+            actions: Vec::new(),
+            target: None,
+            pending_position: None,
+            dropped_at: None,
+            outcome: None,
+        }
+    }
+
+    /// Whether the pointer is currently grabbed for a drag. Pointer and keyboard input should be
+    /// passed to this drag source instead of the handler while this is the case.
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Start dragging `data` from our window. This does nothing if a drag is already in progress.
+    /// `time` is the timestamp of the input that started the drag.
+    pub fn start(
+        &mut self, xcb_connection: &XcbConnection, clipboard: &mut Clipboard, data: DragData,
+        allowed_effects: &[DropEffect], time: Timestamp,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.dragging || self.dropped_at.is_some() {
+            return Ok(());
+        }
+
+        let result = self.try_start(xcb_connection, clipboard, data, allowed_effects, time);
+        if result.is_err() {
+            // The handler still needs to hear that the drag has ended, and the pointer may already
+            // have been grabbed
+            let _ = self.ungrab(xcb_connection);
+            self.outcome = Some(None);
+        }
+
+        result
+    }
+
+    fn try_start(
+        &mut self, xcb_connection: &XcbConnection, clipboard: &mut Clipboard, data: DragData,
+        allowed_effects: &[DropEffect], time: Timestamp,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = &xcb_connection.conn;
+        let atoms = &xcb_connection.atoms;
+
+        let data = match data {
+            DragData::Files(paths) => {
+                ClipboardData::UriList(paths.iter().map(|path| uri_from_path(path)).collect())
+            }
+            DragData::Text(text) => ClipboardData::Text(text),
+        };
+        clipboard.set_contents(xcb_connection, Selection::Drag, vec![data], time)?;
+
+        self.types = clipboard.targets(Selection::Drag);
+        self.actions = allowed_effects
+            .iter()
+            .filter_map(|&effect| action_atom(xcb_connection, effect))
+            .collect();
+        if self.types.is_empty() || self.actions.is_empty() {
+            self.outcome = Some(None);
+            return Ok(());
+        }
+
+        // Targets that want to know about more than three types or about the alternative actions
+        // will read them from these properties
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            atoms.XdndTypeList,
+            AtomEnum::ATOM,
+            &self.types,
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            atoms.XdndActionList,
+            AtomEnum::ATOM,
+            &self.actions,
+        )?;
+
+        let cursor = xcb_connection.get_cursor(MouseCursor::NotAllowed)?;
+        let grab = conn
+            .grab_pointer(
+                false,
+                self.window,
+                EventMask::POINTER_MOTION | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                NONE,
+                cursor,
+                CURRENT_TIME,
+            )?
+            .reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            self.outcome = Some(None);
+            return Ok(());
+        }
+
+        // The keyboard is only needed to cancel the drag with Escape, so this is allowed to fail
+        conn.grab_keyboard(false, self.window, CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC)?;
+        conn.flush()?;
+
+        self.dragging = true;
+
+        Ok(())
+    }
+
+    /// Handle pointer movement while dragging. The coordinates are relative to the root window.
+    pub fn handle_motion(
+        &mut self, xcb_connection: &XcbConnection, root_x: i16, root_y: i16, time: Timestamp,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.dragging {
+            return Ok(());
+        }
+
+        let new_target = find_target(xcb_connection, root_x, root_y)?;
+        if self.target.as_ref().map(|target| target.window) != new_target.map(|(window, _)| window)
+        {
+            self.leave_target(xcb_connection)?;
+            self.pending_position = None;
+
+            if let Some((window, version)) = new_target {
+                let version = version.min(XDND_VERSION);
+                let more_types = (self.types.len() > 3) as u32;
+                let mut data = [self.window, (version << 24) | more_types, NONE, NONE, NONE];
+                for (slot, &atom) in data[2..].iter_mut().zip(&self.types) {
+                    *slot = atom;
+                }
+                send_client_message(xcb_connection, window, xcb_connection.atoms.XdndEnter, data)?;
+
+                self.target = Some(DragTarget {
+                    window,
+                    version,
+                    awaiting_status: false,
+                    accepted_action: None,
+                });
+                self.update_cursor(xcb_connection)?;
+            }
+        }
+
+        self.pending_position = Some((root_x, root_y, time));
+        self.send_pending_position(xcb_connection)
+    }
+
+    fn send_pending_position(
+        &mut self, xcb_connection: &XcbConnection,
+    ) -> Result<(), Box<dyn Error>> {
+        let target = match &mut self.target {
+            Some(target) if !target.awaiting_status => target,
+            _ => return Ok(()),
+        };
+        let (root_x, root_y, time) = match self.pending_position.take() {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+
+        let position = ((root_x as u16 as u32) << 16) | root_y as u16 as u32;
+        send_client_message(
+            xcb_connection,
+            target.window,
+            xcb_connection.atoms.XdndPosition,
+            [self.window, 0, position, time, self.actions[0]],
+        )?;
+        target.awaiting_status = true;
+
+        Ok(())
+    }
+
+    /// Handle an `XdndStatus` or `XdndFinished` reply from the drop target. Returns an event for
+    /// the handler if the drag has finished.
+    pub fn handle_client_message(
+        &mut self, xcb_connection: &XcbConnection, event: &ClientMessageEvent,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        let atoms = &xcb_connection.atoms;
+        let data = event.data.as_data32();
+        let target = match &mut self.target {
+            Some(target) if event.format == 32 && target.window == data[0] => target,
+            _ => return Ok(None),
+        };
+
+        if event.type_ == atoms.XdndStatus {
+            target.awaiting_status = false;
+            target.accepted_action = if data[1] & 1 == 0 {
+                None
+            } else if self.actions.contains(&data[4]) {
+                Some(data[4])
+            } else {
+                // Older targets don't always specify an action, so we'll assume the one we asked
+                // for
+                Some(self.actions[0])
+            };
+
+            if self.dragging {
+                self.update_cursor(xcb_connection)?;
+                self.send_pending_position(xcb_connection)?;
+            }
+
+            Ok(None)
+        } else if event.type_ == atoms.XdndFinished && self.dropped_at.is_some() {
+            // Targets only report whether the drop succeeded starting from version 5
+            let action = if target.version >= 5 {
+                if data[1] & 1 != 0 {
+                    Some(data[2])
+                } else {
+                    None
+                }
+            } else {
+                target.accepted_action
+            };
+            let effect = action.and_then(|action| drop_effect(xcb_connection, action));
+
+            Ok(Some(self.finish(effect)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Drop the data onto the current target after the mouse button has been released.
+    pub fn handle_release(
+        &mut self, xcb_connection: &XcbConnection, time: Timestamp,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        if !self.dragging {
+            return Ok(None);
+        }
+
+        self.ungrab(xcb_connection)?;
+
+        match &self.target {
+            Some(target) if target.accepted_action.is_some() => {
+                send_client_message(
+                    xcb_connection,
+                    target.window,
+                    xcb_connection.atoms.XdndDrop,
+                    [self.window, 0, time, 0, 0],
+                )?;
+                self.dropped_at = Some(Instant::now());
+
+                Ok(None)
+            }
+            _ => {
+                self.leave_target(xcb_connection)?;
+                Ok(Some(self.finish(None)))
+            }
+        }
+    }
+
+    /// Cancel the drag, for instance because Escape was pressed.
+    pub fn cancel(
+        &mut self, xcb_connection: &XcbConnection,
+    ) -> Result<Option<MouseEvent>, Box<dyn Error>> {
+        if !self.dragging {
+            return Ok(None);
+        }
+
+        self.ungrab(xcb_connection)?;
+        self.leave_target(xcb_connection)?;
+
+        Ok(Some(self.finish(None)))
+    }
+
+    /// Whether the data has been dropped and we're waiting on the target to finish the drop.
+    pub fn is_awaiting_finish(&self) -> bool {
+        self.dropped_at.is_some()
+    }
+
+    /// Returns an event for the handler if the drag ended outside of the normal protocol flow, or if
+    /// the target never finished the drop.
+    pub fn poll(&mut self) -> Option<MouseEvent> {
+        if let Some(effect) = self.outcome.take() {
+            return Some(MouseEvent::DragFinished { effect });
+        }
+
+        match self.dropped_at {
+            Some(dropped_at) if dropped_at.elapsed() >= DROP_TIMEOUT => Some(self.finish(None)),
+            _ => None,
+        }
+    }
+
+    fn leave_target(&mut self, xcb_connection: &XcbConnection) -> Result<(), Box<dyn Error>> {
+        if let Some(target) = self.target.take() {
+            send_client_message(
+                xcb_connection,
+                target.window,
+                xcb_connection.atoms.XdndLeave,
+                [self.window, 0, 0, 0, 0],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn ungrab(&mut self, xcb_connection: &XcbConnection) -> Result<(), Box<dyn Error>> {
+        self.dragging = false;
+
+        xcb_connection.conn.ungrab_pointer(CURRENT_TIME)?;
+        xcb_connection.conn.ungrab_keyboard(CURRENT_TIME)?;
+        xcb_connection.conn.flush()?;
+
+        Ok(())
+    }
+
+    /// Show whether the target under the pointer would accept the drop.
+    fn update_cursor(&self, xcb_connection: &XcbConnection) -> Result<(), Box<dyn Error>> {
+        let atoms = &xcb_connection.atoms;
+        let cursor = match self.target.as_ref().and_then(|target| target.accepted_action) {
+            Some(action) if action == atoms.XdndActionMove => MouseCursor::Move,
+            Some(action) if action == atoms.XdndActionLink => MouseCursor::Alias,
+            Some(_) => MouseCursor::Copy,
+            None => MouseCursor::NotAllowed,
+        };
+
+        xcb_connection.conn.change_active_pointer_grab(
+            xcb_connection.get_cursor(cursor)?,
+            CURRENT_TIME,
+            EventMask::POINTER_MOTION | EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE,
+        )?;
+        xcb_connection.conn.flush()?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self, effect: Option<DropEffect>) -> MouseEvent {
+        self.target = None;
+        self.pending_position = None;
+        self.dropped_at = None;
+
+        MouseEvent::DragFinished { effect }
+    }
+}
+
+/// Find the deepest XDND aware window under the pointer, along with the protocol version it
+/// supports. Descending all the way down instead of stopping at the top level window lets us drop
+/// onto windows embedded in other applications, like plugin editors.
+fn find_target(
+    xcb_connection: &XcbConnection, root_x: i16, root_y: i16,
+) -> Result<Option<(XWindow, u32)>, Box<dyn Error>> {
+    let conn = &xcb_connection.conn;
+    let root = xcb_connection.screen().root;
+
+    let mut window = root;
+    let mut target = None;
+    loop {
+        let child = conn.translate_coordinates(root, window, root_x, root_y)?.reply()?.child;
+        if child == NONE {
+            return Ok(target);
+        }
+        window = child;
+
+        let version = conn
+            .get_property(false, window, xcb_connection.atoms.XdndAware, AtomEnum::ATOM, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut value| value.next());
+        match version {
+            Some(version) if version >= MIN_XDND_VERSION => target = Some((window, version)),
+            _ => (),
+        }
+    }
+}
+
+fn action_atom(xcb_connection: &XcbConnection, effect: DropEffect) -> Option<Atom> {
+    let atoms = &xcb_connection.atoms;
+    match effect {
+        DropEffect::Copy => Some(atoms.XdndActionCopy),
+        DropEffect::Move => Some(atoms.XdndActionMove),
+        DropEffect::Link => Some(atoms.XdndActionLink),
+        // XDND has no notion of scrolling the target
+        DropEffect::Scroll => None,
+    }
+}
+
+fn drop_effect(xcb_connection: &XcbConnection, action: Atom) -> Option<DropEffect> {
+    let atoms = &xcb_connection.atoms;
+    if action == atoms.XdndActionCopy {
+        Some(DropEffect::Copy)
+    } else if action == atoms.XdndActionMove {
+        Some(DropEffect::Move)
+    } else if action == atoms.XdndActionLink {
+        Some(DropEffect::Link)
+    } else {
+        None
+    }
+}
+
+/// Ask the source to convert the dragged data to `target`, which it will write to a property on
+/// our window.
+fn request_data(
+    xcb_connection: &XcbConnection, window: XWindow, session: &mut DragSession, target: Atom,
+    timestamp: Timestamp,
+) -> Result<(), Box<dyn Error>> {
+    xcb_connection.conn.convert_selection(
+        window,
+        xcb_connection.atoms.XdndSelection,
+        target,
+        xcb_connection.atoms.BASEVIEW_DRAG_DATA,
+        timestamp,
+    )?;
+    xcb_connection.conn.flush()?;
+
+    session.request = Some(DataRequest { target, incr_data: None, last_activity: Instant::now() });
+
+    Ok(())
+}
+
+/// Pick the first of the offered types that looks like a MIME type. Its payload will be fetched
+/// once the data is dropped.
+fn mime_data(xcb_connection: &XcbConnection, types: &[Atom]) -> Result<DropData, Box<dyn Error>> {
+    for &atom in types {
+        let mime = xcb_connection.atom_name(atom)?;
+        if mime.contains('/') {
+            return Ok(DropData::Mime { mime, data: None });
+        }
+    }
+
+    Ok(DropData::None)
+}
+
+fn send_client_message(
+    xcb_connection: &XcbConnection, window: XWindow, type_: Atom, data: [u32; 5],
+) -> Result<(), Box<dyn Error>> {
+    let event = ClientMessageEvent::new(32, window, type_, data);
+    xcb_connection.conn.send_event(false, window, EventMask::NO_EVENT, event)?;
+    xcb_connection.conn.flush()?;
+
+    Ok(())
+}
+
+/// Convert a `file://` URI from a `text/uri-list` to a path. Other kinds of URIs are ignored, and
+/// so are files on other hosts than `hostname`, the name of the local host.
+fn path_from_uri(uri: &str, hostname: Option<&str>) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // The host name is usually empty or `localhost`
+    let (host, path) = rest.split_at(rest.find('/')?);
+    let is_local = host.is_empty()
+        || host.eq_ignore_ascii_case("localhost")
+        || hostname.map_or(false, |hostname| host.eq_ignore_ascii_case(hostname));
+    if !is_local {
+        return None;
+    }
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' {
+            path.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    Some(OsString::from_vec(decoded).into())
+}
+
+/// The name of the local host, for telling local `file://` URIs apart from remote ones.
+fn local_hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let hostname = nix::unistd::gethostname(&mut buffer).ok()?;
+
+    hostname.to_str().ok().map(str::to_owned)
+}
+
+/// Convert a path to a `file://` URI for a `text/uri-list`, percent-encoding everything but the
+/// characters that are allowed in paths.
+fn uri_from_path(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_file_uris_are_paths() {
+        let hostname = Some("studio");
+
+        assert_eq!(path_from_uri("file:///tmp/a%20b", hostname), Some("/tmp/a b".into()));
+        assert_eq!(path_from_uri("file://localhost/tmp/a", hostname), Some("/tmp/a".into()));
+        assert_eq!(path_from_uri("file://studio/tmp/a", hostname), Some("/tmp/a".into()));
+    }
+
+    #[test]
+    fn remote_and_other_uris_are_not_paths() {
+        assert_eq!(path_from_uri("file://laptop/tmp/a", Some("studio")), None);
+        assert_eq!(path_from_uri("file://laptop/tmp/a", None), None);
+        assert_eq!(path_from_uri("https://example.com/a", Some("studio")), None);
+        assert_eq!(path_from_uri("file://studio", Some("studio")), None);
+    }
+
+    #[test]
+    fn invalid_escapes_are_kept() {
+        assert_eq!(path_from_uri("file:///tmp/100%", None), Some("/tmp/100%".into()));
+        assert_eq!(path_from_uri("file:///tmp/%zz", None), Some("/tmp/%zz".into()));
+    }
+
+    #[test]
+    fn paths_round_trip_through_uris() {
+        let path = Path::new("/home/user/Samples/Kick 01 (100%) é.wav");
+        let uri = uri_from_path(path);
+
+        assert_eq!(uri, "file:///home/user/Samples/Kick%2001%20%28100%25%29%20%C3%A9.wav");
+        assert_eq!(path_from_uri(&uri, None).as_deref(), Some(path));
+
+        // Paths don't need to be valid UTF-8
+        let path = PathBuf::from(OsString::from_vec(b"/tmp/\xff".to_vec()));
+        assert_eq!(path_from_uri(&uri_from_path(&path), None), Some(path));
+    }
+}
@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::Arc;
+use std::time::Instant;
+
+use nix::sys::epoll::{
+    epoll_create1, epoll_ctl, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+};
+
+use super::dispatcher::Dispatcher;
+use super::waker::Waker;
+
+/// The event loop of an embedded window. Plugin hosts that want the editor to run on their own GUI
+/// thread (CLAP's `posix-fd-support` and `timer-support` extensions, or VST3's `IRunLoop`) drive
+/// this loop by watching [`fd()`][Self::fd()] and by calling [`on_timer()`][Self::on_timer()]
+/// when the [`timer_deadline()`][Self::timer_deadline()] has passed. The window has a
+/// [`Dispatcher`] and an X11 connection of its own, so it's unaffected by other windows living on
+/// the dispatcher thread.
+pub struct EmbeddedWindow {
+    dispatcher: Dispatcher,
+    /// The window's waker, which becomes readable when another thread sends the window a message.
+    waker: Arc<Waker>,
+    /// An epoll instance watching both the X11 connection and the waker, so the host only needs to
+    /// watch a single file descriptor.
+    epoll_fd: RawFd,
+}
+
+impl EmbeddedWindow {
+    pub(super) fn new(dispatcher: Dispatcher, waker: Arc<Waker>) -> Result<Self, Box<dyn Error>> {
+        let epoll_fd = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)?;
+        let embedded_window = Self { dispatcher, waker, epoll_fd };
+
+        let xcb_fd = embedded_window.dispatcher.connection().conn.as_raw_fd();
+        for fd in [xcb_fd, embedded_window.waker.fd()].iter().copied() {
+            let mut event = EpollEvent::new(EpollFlags::EPOLLIN, 0);
+            epoll_ctl(epoll_fd, EpollOp::EpollCtlAdd, fd, &mut event)?;
+        }
+
+        Ok(embedded_window)
+    }
+
+    pub(super) fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.epoll_fd
+    }
+
+    pub fn timer_deadline(&self) -> Option<Instant> {
+        self.dispatcher.poll_timeout().map(|timeout| Instant::now() + timeout)
+    }
+
+    pub fn pump_events(&self) {
+        // The epoll instance is level triggered, so the waker needs to be reset before handling
+        // the messages to stop it from staying readable
+        self.waker.reset();
+        self.dispatcher.dispatch();
+    }
+
+    pub fn on_timer(&self) {
+        self.dispatcher.dispatch();
+    }
+}
+
+impl Drop for EmbeddedWindow {
+    fn drop(&mut self) {
+        // Nothing will pump the window's events after this, so it needs to close right away. This
+        // blocks for up to `CLIPBOARD_SAVE_TIMEOUT` if a clipboard manager takes over the
+        // clipboard.
+        self.dispatcher.close_windows();
+
+        let _ = nix::unistd::close(self.epoll_fd);
+    }
+}
@@ -1,23 +1,60 @@
+use crate::x11::clipboard;
 use crate::x11::keyboard::{convert_key_press_event, convert_key_release_event, key_mods};
-use crate::x11::{ParentHandle, Window, WindowInner};
+use crate::x11::{ParentHandle, Window, WindowInner, WindowMessage};
 use crate::{
-    Event, MouseButton, MouseEvent, PhyPoint, PhySize, ScrollDelta, WindowEvent, WindowHandler,
-    WindowInfo,
+    ClipboardEvent, Event, EventStatus, FrameClock, MouseButton, MouseEvent, PhyPoint, PhyRect,
+    PhySize, RedrawPolicy, ScrollDelta, WindowEvent, WindowHandler, WindowInfo,
 };
+use keyboard_types::{Key, NamedKey};
 use std::error::Error;
-use std::os::fd::AsRawFd;
+use std::os::fd::RawFd;
 use std::time::{Duration, Instant};
 use x11rb::connection::Connection;
+use x11rb::protocol::present::{self, CompleteKind, ConnectionExt as _};
+use x11rb::protocol::xproto::{ConnectionExt as _, NotifyDetail, NotifyMode, Window as XWindow};
 use x11rb::protocol::Event as XEvent;
 
+/// How long we'll wait for a clipboard manager to take over the clipboard's contents when the
+/// window closes.
+const CLIPBOARD_SAVE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many frame intervals we'll wait for a vertical blank before drawing frames on the timer
+/// instead. The X server won't ever send the `CompleteNotify` event if it failed to handle our
+/// request, so without this the window would stop drawing altogether.
+const VSYNC_TIMEOUT_FRAMES: u32 = 4;
+
+/// The state of a single window's event loop. The [`Dispatcher`][super::dispatcher::Dispatcher]
+/// feeds it the events meant for its window, and asks it to draw frames when they're due.
 pub(super) struct EventLoop {
     handler: Box<dyn WindowHandler>,
     window: WindowInner,
     parent_handle: Option<ParentHandle>,
 
     new_physical_size: Option<PhySize>,
-    frame_interval: Duration,
+    /// Set when the window may have moved since the last `WindowEvent::Moved` event.
+    position_changed: bool,
+    /// The parts of the window that need to be drawn again, collected from `Expose` events until
+    /// the next frame.
+    damage: Vec<PhyRect>,
+    last_frame: Instant,
     event_loop_running: bool,
+    /// Set while a clipboard manager is taking over the clipboard's contents after the window has
+    /// closed. The event loop is kept around until the clipboard manager is done, or until this
+    /// deadline has passed.
+    clipboard_save_deadline: Option<Instant>,
+
+    /// Whether frames are paced by the Present extension's `CompleteNotify` events instead of by
+    /// a timer.
+    vsync: bool,
+    /// The MSC (the display's frame counter) of the last vertical blank we were notified of.
+    vsync_msc: u64,
+    /// Whether we've been notified of a vertical blank we haven't handled yet.
+    vsync_frame_ready: bool,
+    /// When we asked to be notified of the next vertical blank, if we're still waiting on it.
+    vsync_frame_requested: Option<Instant>,
+    /// Set when a vertical blank we asked for is overdue. Frames are drawn on the timer until the
+    /// X server reports a vertical blank again.
+    vsync_stalled: bool,
 }
 
 impl EventLoop {
@@ -25,27 +62,91 @@ impl EventLoop {
         window: WindowInner, handler: impl WindowHandler + 'static,
         parent_handle: Option<ParentHandle>,
     ) -> Self {
-        Self {
+        let mut event_loop = Self {
             window,
             handler: Box::new(handler),
             parent_handle,
-            frame_interval: Duration::from_millis(15),
-            event_loop_running: false,
+            event_loop_running: true,
+            clipboard_save_deadline: None,
             new_physical_size: None,
+            position_changed: false,
+            damage: Vec::new(),
+            last_frame: Instant::now(),
+            vsync: false,
+            vsync_msc: 0,
+            vsync_frame_ready: false,
+            vsync_frame_requested: None,
+            vsync_stalled: false,
+        };
+
+        if event_loop.window.frame_clock == FrameClock::Vsync {
+            event_loop.vsync = event_loop.start_vsync().is_ok();
         }
+
+        // The handler may have copied something to the clipboard while it was being built
+        event_loop.claim_pending_copies();
+
+        event_loop
+    }
+
+    pub fn window_id(&self) -> XWindow {
+        self.window.window_id
+    }
+
+    /// Whether the window has closed and is done handing the clipboard off to a clipboard manager.
+    /// Once this returns `true`, the event loop should be dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.event_loop_running && self.clipboard_save_deadline.is_none()
+    }
+
+    /// The file descriptor that becomes readable when another thread sends the window a message.
+    pub fn waker_fd(&self) -> RawFd {
+        self.window.event_proxy.waker.fd()
     }
 
-    #[inline]
-    fn drain_xcb_events(&mut self) -> Result<(), Box<dyn Error>> {
-        // the X server has a tendency to send spurious/extraneous configure notify events when a
-        // window is resized, and we need to batch those together and just send one resize event
-        // when they've all been coalesced.
-        self.new_physical_size = None;
+    pub fn reset_waker(&self) {
+        self.window.event_proxy.waker.reset();
+    }
 
-        while let Some(event) = self.window.xcb_connection.conn.poll_for_event()? {
-            self.handle_xcb_event(event);
+    /// Call the handler's `on_frame()` if the window wants to be drawn and the next frame is due.
+    pub fn draw_frame_if_due(&mut self) {
+        if !self.event_loop_running {
+            return;
+        }
+
+        // We'll try to keep a consistent frame pace. If the last frame couldn't be processed in
+        // the expected frame time, this will throttle down to prevent multiple frames from
+        // being queued up. The conditional here is needed because event handling and frame
+        // drawing is interleaved. The dispatcher's `poll()` call will wait until the next frame
+        // can be drawn, or until a window receives an event. We thus need to manually check if
+        // it's already time to draw a new frame.
+        //
+        // With a vsync frame clock frames are instead drawn after the display's vertical
+        // blank, unless the X server hasn't reported one in a while.
+        if self.vsync && !self.vsync_stalled {
+            if self.vsync_frame_ready {
+                self.vsync_frame_ready = false;
+                if self.wants_frame() {
+                    self.draw_frame();
+                    self.last_frame = Instant::now();
+                }
+            }
+        } else {
+            let frame_interval = self.window.frame_interval.get();
+            let next_frame = self.last_frame + frame_interval;
+            if self.wants_frame() && Instant::now() >= next_frame {
+                self.draw_frame();
+                self.last_frame = Instant::max(next_frame, Instant::now() - frame_interval);
+            }
         }
 
+        // The handler may have copied something to the clipboard during the frame
+        self.claim_pending_copies();
+    }
+
+    /// Send the handler a single resize and a single move event for all of the `ConfigureNotify`
+    /// events received since the last call.
+    pub fn flush_configure(&mut self) {
         if let Some(size) = self.new_physical_size.take() {
             self.window.window_info =
                 WindowInfo::from_physical_size(size, self.window.window_info.scale());
@@ -56,76 +157,106 @@ impl EventLoop {
                 &mut crate::Window::new(Window { inner: &self.window }),
                 Event::Window(WindowEvent::Resized(window_info)),
             );
-        }
 
-        Ok(())
-    }
+            self.claim_pending_copies();
+        }
 
-    // Event loop
-    // FIXME: poll() acts fine on linux, sometimes funky on *BSD. XCB upstream uses a define to
-    // switch between poll() and select() (the latter of which is fine on *BSD), and we should do
-    // the same.
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        use nix::poll::*;
+        // `ConfigureNotify` events for reparented windows are relative to the window manager's
+        // frame, so we need to ask the X server where the window ended up
+        if self.position_changed {
+            self.position_changed = false;
 
-        let xcb_fd = self.window.xcb_connection.conn.as_raw_fd();
+            match self.window.query_position() {
+                Ok(position) if position != self.window.position.get() => {
+                    self.window.position.set(position);
 
-        let mut last_frame = Instant::now();
-        self.event_loop_running = true;
+                    let position = position.to_logical(&self.window.window_info);
+                    self.handler.on_event(
+                        &mut crate::Window::new(Window { inner: &self.window }),
+                        Event::Window(WindowEvent::Moved(position)),
+                    );
 
-        while self.event_loop_running {
-            // We'll try to keep a consistent frame pace. If the last frame couldn't be processed in
-            // the expected frame time, this will throttle down to prevent multiple frames from
-            // being queued up. The conditional here is needed because event handling and frame
-            // drawing is interleaved. The `poll()` function below will wait until the next frame
-            // can be drawn, or until the window receives an event. We thus need to manually check
-            // if it's already time to draw a new frame.
-            let next_frame = last_frame + self.frame_interval;
-            if Instant::now() >= next_frame {
-                self.handler.on_frame(&mut crate::Window::new(Window { inner: &self.window }));
-                last_frame = Instant::max(next_frame, Instant::now() - self.frame_interval);
+                    self.claim_pending_copies();
+                }
+                _ => {}
             }
+        }
+    }
 
-            let mut fds = [PollFd::new(xcb_fd, PollFlags::POLLIN)];
+    /// Housekeeping done after every round of events: timing out transfers, delivering messages
+    /// from other threads, and closing the window if that has been requested.
+    pub fn update(&mut self) {
+        if !self.event_loop_running {
+            if self.clipboard_save_deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                self.clipboard_save_deadline = None;
+            }
 
-            // Check for any events in the internal buffers
-            // before going to sleep:
-            self.drain_xcb_events()?;
+            return;
+        }
 
-            // FIXME: handle errors
-            poll(&mut fds, next_frame.duration_since(Instant::now()).subsec_millis() as i32)
-                .unwrap();
+        self.update_clipboard();
+        self.update_drag_and_drop();
+        self.handle_messages();
 
-            if let Some(revents) = fds[0].revents() {
-                if revents.contains(PollFlags::POLLERR) {
-                    panic!("xcb connection poll error");
-                }
+        // The handler may have copied something to the clipboard in response to any of the above
+        self.claim_pending_copies();
 
-                if revents.contains(PollFlags::POLLIN) {
-                    self.drain_xcb_events()?;
-                }
+        if self.vsync && self.wants_frame() {
+            // The X server may have dropped an overdue request, so we'll ask again while drawing
+            // frames on the timer in the meantime
+            let overdue =
+                self.vsync_deadline().map_or(false, |deadline| Instant::now() >= deadline);
+            if overdue {
+                self.vsync_stalled = true;
             }
 
-            // Check if the parents's handle was dropped (such as when the host
-            // requested the window to close)
-            if let Some(parent_handle) = &self.parent_handle {
-                if parent_handle.parent_did_drop() {
-                    self.handle_must_close();
-                    self.window.close_requested.set(false);
-                }
+            if (self.vsync_frame_requested.is_none() || overdue)
+                && self.request_vsync_frame(self.vsync_msc + 1).is_ok()
+            {
+                self.vsync_frame_requested = Some(Instant::now());
             }
+        }
 
-            // Check if the user has requested the window to close
-            if self.window.close_requested.get() {
+        // Check if the parents's handle was dropped (such as when the host
+        // requested the window to close)
+        if let Some(parent_handle) = &self.parent_handle {
+            if parent_handle.parent_did_drop() {
                 self.handle_must_close();
                 self.window.close_requested.set(false);
             }
         }
 
-        Ok(())
+        // Check if the user has requested the window to close
+        if self.window.close_requested.get() {
+            self.handle_must_close();
+            self.window.close_requested.set(false);
+        }
+    }
+
+    /// Close the window without waiting for the next call to [`update()`][Self::update()].
+    pub fn close(&mut self) {
+        if self.event_loop_running {
+            self.handle_must_close();
+        }
+    }
+
+    pub fn handle_xcb_event(&mut self, event: XEvent) {
+        // Events can still trickle in after the window has closed
+        if !self.event_loop_running {
+            if self.clipboard_save_deadline.is_some() {
+                self.handle_clipboard_save_event(&event);
+            }
+
+            return;
+        }
+
+        self.process_xcb_event(event);
+
+        // The handler may have copied something to the clipboard while handling the event
+        self.claim_pending_copies();
     }
 
-    fn handle_xcb_event(&mut self, event: XEvent) {
+    fn process_xcb_event(&mut self, event: XEvent) {
         // For all the keyboard and mouse events, you can fetch
         // `x`, `y`, `detail`, and `state`.
         // - `x` and `y` are the position inside the window where the cursor currently is
@@ -146,19 +277,199 @@ impl EventLoop {
         //   the keyboard modifier keys at the time of the event.
         //   http://rtbo.github.io/rust-xcb/src/xcb/ffi/xproto.rs.html#445
 
+        match &event {
+            XEvent::KeyPress(event) | XEvent::KeyRelease(event) => {
+                self.window.last_input_time.set(event.time)
+            }
+            XEvent::ButtonPress(event) | XEvent::ButtonRelease(event) => {
+                self.window.last_input_time.set(event.time)
+            }
+            _ => {}
+        }
+
+        // While dragging something out of the window, the pointer and keyboard steer the drag
+        if self.window.drag_source.borrow().is_dragging() && self.handle_drag_source_input(&event) {
+            return;
+        }
+
         match event {
             ////
             // window
             ////
-            XEvent::ClientMessage(event) => {
+            XEvent::ClientMessage(event)
                 if event.format == 32
+                    && event.type_ == self.window.xcb_connection.atoms.WM_PROTOCOLS
                     && event.data.as_data32()[0]
-                        == self.window.xcb_connection.atoms.WM_DELETE_WINDOW
-                {
-                    self.handle_close_requested();
+                        == self.window.xcb_connection.atoms.WM_DELETE_WINDOW =>
+            {
+                self.handle_close_requested();
+            }
+
+            // With `WM_TAKE_FOCUS` the window manager leaves it up to us to accept focus
+            XEvent::ClientMessage(event)
+                if event.format == 32
+                    && event.type_ == self.window.xcb_connection.atoms.WM_PROTOCOLS
+                    && event.data.as_data32()[0]
+                        == self.window.xcb_connection.atoms.WM_TAKE_FOCUS =>
+            {
+                self.window.set_input_focus(event.data.as_data32()[1]);
+            }
+
+            XEvent::ClientMessage(event)
+                if event.type_ == self.window.xcb_connection.atoms.XdndStatus
+                    || event.type_ == self.window.xcb_connection.atoms.XdndFinished =>
+            {
+                let result = self
+                    .window
+                    .drag_source
+                    .borrow_mut()
+                    .handle_client_message(&self.window.xcb_connection, &event);
+                if let Ok(Some(event)) = result {
+                    self.handler.on_event(
+                        &mut crate::Window::new(Window { inner: &self.window }),
+                        Event::Mouse(event),
+                    );
+                }
+            }
+
+            XEvent::ClientMessage(event) => {
+                let result = self.window.drop_target.borrow_mut().handle_client_message(
+                    &self.window.xcb_connection,
+                    &self.window.window_info,
+                    &event,
+                );
+                if let Ok(Some(event)) = result {
+                    self.handle_drag_event(event);
+                }
+            }
+
+            XEvent::SelectionRequest(event) => {
+                let _ = self
+                    .window
+                    .clipboard
+                    .borrow_mut()
+                    .handle_selection_request(&self.window.xcb_connection, &event);
+            }
+
+            XEvent::SelectionClear(event) => {
+                self.window
+                    .clipboard
+                    .borrow_mut()
+                    .handle_selection_clear(&self.window.xcb_connection, &event);
+            }
+
+            XEvent::SelectionNotify(event)
+                if event.selection == self.window.xcb_connection.atoms.XdndSelection =>
+            {
+                let result = self
+                    .window
+                    .drop_target
+                    .borrow_mut()
+                    .handle_selection_notify(&self.window.xcb_connection, &event);
+                if let Ok(Some(event)) = result {
+                    self.handle_drag_event(event);
+                }
+            }
+
+            XEvent::SelectionNotify(event) => {
+                let result = self
+                    .window
+                    .clipboard
+                    .borrow_mut()
+                    .handle_selection_notify(&self.window.xcb_connection, &event);
+                if let Ok(Some(event)) = result {
+                    self.handler.on_event(
+                        &mut crate::Window::new(Window { inner: &self.window }),
+                        Event::Clipboard(event),
+                    );
+                }
+            }
+
+            XEvent::XfixesSelectionNotify(event)
+                if event.selection == self.window.xcb_connection.atoms.CLIPBOARD =>
+            {
+                self.handler.on_event(
+                    &mut crate::Window::new(Window { inner: &self.window }),
+                    Event::Clipboard(ClipboardEvent::Changed),
+                );
+            }
+
+            XEvent::PropertyNotify(event)
+                if event.window == self.window.window_id
+                    && event.atom == self.window.xcb_connection.atoms.BASEVIEW_DRAG_DATA =>
+            {
+                let result = self
+                    .window
+                    .drop_target
+                    .borrow_mut()
+                    .handle_property_notify(&self.window.xcb_connection, &event);
+                if let Ok(Some(event)) = result {
+                    self.handle_drag_event(event);
+                }
+            }
+
+            // The size of the window manager's decorations changes where the frame is in relation
+            // to the window
+            XEvent::PropertyNotify(event)
+                if event.window == self.window.window_id
+                    && event.atom == self.window.xcb_connection.atoms._NET_FRAME_EXTENTS =>
+            {
+                if let Ok(frame_offset) = self.window.query_frame_offset() {
+                    self.window.frame_offset.set(frame_offset);
+                    self.position_changed = true;
+                }
+            }
+
+            XEvent::PropertyNotify(event) => {
+                let result = self
+                    .window
+                    .clipboard
+                    .borrow_mut()
+                    .handle_property_notify(&self.window.xcb_connection, &event);
+                if let Ok(Some(event)) = result {
+                    self.handler.on_event(
+                        &mut crate::Window::new(Window { inner: &self.window }),
+                        Event::Clipboard(event),
+                    );
                 }
             }
 
+            // Focus changes caused by keyboard grabs, or that only move focus around within our
+            // window, don't change whether the window has focus
+            XEvent::FocusIn(event) | XEvent::FocusOut(event)
+                if event.mode == NotifyMode::GRAB
+                    || event.mode == NotifyMode::UNGRAB
+                    || event.detail == NotifyDetail::INFERIOR
+                    || event.detail == NotifyDetail::POINTER => {}
+
+            XEvent::FocusIn(_) => self.set_focus(true),
+
+            XEvent::FocusOut(_) => self.set_focus(false),
+
+            XEvent::PresentCompleteNotify(event)
+                if event.window == self.window.window_id
+                    && event.kind == CompleteKind::NOTIFY_MSC =>
+            {
+                self.vsync_msc = event.msc;
+                self.vsync_frame_ready = true;
+                self.vsync_frame_requested = None;
+                self.vsync_stalled = false;
+            }
+
+            // Parts of the window need to be drawn again
+            XEvent::Expose(event) => {
+                self.damage.push(PhyRect::new(
+                    event.x as i32,
+                    event.y as i32,
+                    event.width as u32,
+                    event.height as u32,
+                ));
+                self.window.redraw_requested.set(true);
+            }
+
+            // This happens when the parent window gets destroyed before our window was closed
+            XEvent::DestroyNotify(_) => self.handle_must_close(),
+
             XEvent::ConfigureNotify(event) => {
                 let new_physical_size = PhySize::new(event.width as u32, event.height as u32);
 
@@ -167,6 +478,8 @@ impl EventLoop {
                 {
                     self.new_physical_size = Some(new_physical_size);
                 }
+
+                self.position_changed = true;
             }
 
             ////
@@ -238,17 +551,15 @@ impl EventLoop {
                 }
             },
 
-            XEvent::ButtonRelease(event) => {
-                if !(4..=7).contains(&event.detail) {
-                    let button_id = mouse_id(event.detail);
-                    self.handler.on_event(
-                        &mut crate::Window::new(Window { inner: &self.window }),
-                        Event::Mouse(MouseEvent::ButtonReleased {
-                            button: button_id,
-                            modifiers: key_mods(event.state),
-                        }),
-                    );
-                }
+            XEvent::ButtonRelease(event) if !(4..=7).contains(&event.detail) => {
+                let button_id = mouse_id(event.detail);
+                self.handler.on_event(
+                    &mut crate::Window::new(Window { inner: &self.window }),
+                    Event::Mouse(MouseEvent::ButtonReleased {
+                        button: button_id,
+                        modifiers: key_mods(event.state),
+                    }),
+                );
             }
 
             ////
@@ -272,9 +583,247 @@ impl EventLoop {
         }
     }
 
+    /// Take ownership of the selections for the data the handler passed to
+    /// [`copy_to_clipboard()`][crate::copy_to_clipboard()] and friends. Since all windows'
+    /// handlers run on the same thread, this needs to be called right after calling into the
+    /// handler.
+    fn claim_pending_copies(&mut self) {
+        for (selection, data) in clipboard::take_pending_copies() {
+            let _ = self.window.clipboard.borrow_mut().set_contents(
+                &self.window.xcb_connection,
+                selection,
+                data,
+                self.window.last_input_time.get(),
+            );
+        }
+    }
+
+    fn update_clipboard(&mut self) {
+        let event =
+            self.window.clipboard.borrow_mut().expire_stale_read(&self.window.xcb_connection);
+        if let Some(event) = event {
+            self.handler.on_event(
+                &mut crate::Window::new(Window { inner: &self.window }),
+                Event::Clipboard(event),
+            );
+        }
+
+        // The handler is still owed a response for the conversions we couldn't request
+        loop {
+            let event = self.window.clipboard.borrow_mut().take_failed_read();
+            match event {
+                Some(event) => {
+                    self.handler.on_event(
+                        &mut crate::Window::new(Window { inner: &self.window }),
+                        Event::Clipboard(event),
+                    );
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Deliver the events sent through the window's [`EventProxy`][crate::EventProxy]s, and run
+    /// the closures passed to
+    /// [WindowHandle::run_on_window_thread](`crate::WindowHandle::run_on_window_thread()`).
+    fn handle_messages(&mut self) {
+        while let Ok(message) = self.window.messages.try_recv() {
+            let mut window = crate::Window::new(Window { inner: &self.window });
+
+            match message {
+                WindowMessage::User(event) => {
+                    self.handler.on_event(&mut window, Event::User(event));
+                }
+                WindowMessage::Run(task) => task(&mut window, &mut *self.handler),
+            }
+        }
+    }
+
+    /// Whether the handler's `on_frame()` should be called when the next frame is due.
+    fn wants_frame(&self) -> bool {
+        match self.window.redraw_policy {
+            RedrawPolicy::Continuous => true,
+            RedrawPolicy::OnDemand => self.window.redraw_requested.get(),
+        }
+    }
+
+    fn draw_frame(&mut self) {
+        self.window.redraw_requested.set(false);
+
+        if !self.damage.is_empty() {
+            let damage = std::mem::take(&mut self.damage);
+
+            self.handler.on_event(
+                &mut crate::Window::new(Window { inner: &self.window }),
+                Event::Window(WindowEvent::Damaged(damage)),
+            );
+        }
+
+        self.handler.on_frame(&mut crate::Window::new(Window { inner: &self.window }));
+    }
+
+    /// How long the dispatcher's `poll()` call may block for as far as this window is concerned,
+    /// or `None` if it can block until the next event.
+    pub fn poll_timeout(&self) -> Option<Duration> {
+        if !self.event_loop_running {
+            return self
+                .clipboard_save_deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        }
+
+        // A vertical blank may have been reported while draining the events, in which case we
+        // shouldn't wait before drawing the next frame. The same goes for clipboard responses the
+        // handler is still waiting on.
+        if self.vsync_frame_ready || self.window.clipboard.borrow().has_failed_reads() {
+            return Some(Duration::ZERO);
+        }
+
+        if self.wants_frame() {
+            let next_frame = if self.vsync && !self.vsync_stalled {
+                // We still need to wake up to notice when the vertical blank doesn't arrive
+                self.vsync_deadline()
+            } else {
+                Some(self.last_frame + self.window.frame_interval.get())
+            };
+
+            if let Some(next_frame) = next_frame {
+                return Some(next_frame.saturating_duration_since(Instant::now()));
+            }
+        }
+
+        // Clipboard reads and drag and drop transfers give up on unresponsive peers after a
+        // timeout, so we need to keep waking up while one of those is in progress
+        let waiting_on_timeout = self.window.clipboard.borrow().is_reading()
+            || self.window.drag_source.borrow().is_awaiting_finish()
+            || self.window.drop_target.borrow().is_fetching_data();
+        if waiting_on_timeout {
+            return Some(self.window.frame_interval.get());
+        }
+
+        None
+    }
+
+    /// When we'll give up on the vertical blank we've asked to be notified of, if any.
+    fn vsync_deadline(&self) -> Option<Instant> {
+        self.vsync_frame_requested
+            .map(|requested| requested + self.window.frame_interval.get() * VSYNC_TIMEOUT_FRAMES)
+    }
+
+    /// Select the Present extension's `CompleteNotify` events for our window. Fails if the
+    /// extension isn't available.
+    fn start_vsync(&self) -> Result<(), Box<dyn Error>> {
+        let xcb_connection = &self.window.xcb_connection;
+        let conn = &xcb_connection.conn;
+
+        if !xcb_connection.has_present {
+            return Err("The Present extension is not available".into());
+        }
+
+        let eid = conn.generate_id()?;
+        conn.present_select_input(eid, self.window.window_id, present::EventMask::COMPLETE_NOTIFY)?;
+
+        Ok(())
+    }
+
+    /// Ask the X server to send a `CompleteNotify` event once the display's frame counter reaches
+    /// `target_msc`, or at the next vertical blank if it already has.
+    fn request_vsync_frame(&self, target_msc: u64) -> Result<(), Box<dyn Error>> {
+        let conn = &self.window.xcb_connection.conn;
+
+        conn.present_notify_msc(self.window.window_id, 0, target_msc, 1, 0)?;
+        conn.flush()?;
+
+        Ok(())
+    }
+
+    fn set_focus(&mut self, has_focus: bool) {
+        if self.window.has_focus.replace(has_focus) == has_focus {
+            return;
+        }
+
+        self.handler.on_event(
+            &mut crate::Window::new(Window { inner: &self.window }),
+            Event::Window(if has_focus { WindowEvent::Focused } else { WindowEvent::Unfocused }),
+        );
+    }
+
+    /// Handle pointer and keyboard input during a drag started by the handler. Returns `false` if
+    /// the event should still be handled as usual.
+    fn handle_drag_source_input(&mut self, event: &XEvent) -> bool {
+        let xcb_connection = &self.window.xcb_connection;
+        let mut drag_source = self.window.drag_source.borrow_mut();
+
+        let mut ends_drag = false;
+        let result = match event {
+            XEvent::MotionNotify(event) => drag_source
+                .handle_motion(xcb_connection, event.root_x, event.root_y, event.time)
+                .map(|()| None),
+            XEvent::ButtonRelease(event) if !(4..=7).contains(&event.detail) => {
+                ends_drag = true;
+                drag_source.handle_release(xcb_connection, event.time)
+            }
+            XEvent::KeyPress(event)
+                if convert_key_press_event(event).key == Key::Named(NamedKey::Escape) =>
+            {
+                drag_source.cancel(xcb_connection)
+            }
+            XEvent::ButtonPress(_)
+            | XEvent::ButtonRelease(_)
+            | XEvent::KeyPress(_)
+            | XEvent::KeyRelease(_) => Ok(None),
+            _ => return false,
+        };
+        drop(drag_source);
+
+        if let Ok(Some(event)) = result {
+            self.handler.on_event(
+                &mut crate::Window::new(Window { inner: &self.window }),
+                Event::Mouse(event),
+            );
+        }
+
+        // The handler still gets to see the release of the button that started the drag, so
+        // widgets don't stay pressed
+        !ends_drag
+    }
+
+    /// Report drags that ended outside of the usual protocol flow to the handler, and stop waiting
+    /// on drag sources that have stopped sending their data.
+    fn update_drag_and_drop(&mut self) {
+        let event = self.window.drag_source.borrow_mut().poll();
+        if let Some(event) = event {
+            self.handler.on_event(
+                &mut crate::Window::new(Window { inner: &self.window }),
+                Event::Mouse(event),
+            );
+        }
+
+        let event =
+            self.window.drop_target.borrow_mut().expire_stale_request(&self.window.xcb_connection);
+        if let Some(event) = event {
+            self.handle_drag_event(event);
+        }
+    }
+
+    /// Forward a drag and drop event to the handler, and let the drag source know whether the
+    /// handler accepts the drop.
+    fn handle_drag_event(&mut self, event: MouseEvent) {
+        let status = self
+            .handler
+            .on_event(&mut crate::Window::new(Window { inner: &self.window }), Event::Mouse(event));
+
+        let _ = self.window.drop_target.borrow_mut().respond(&self.window.xcb_connection, status);
+    }
+
     fn handle_close_requested(&mut self) {
-        // FIXME: handler should decide whether window stays open or not
-        self.handle_must_close();
+        let status = self.handler.on_event(
+            &mut crate::Window::new(Window { inner: &self.window }),
+            Event::Window(WindowEvent::CloseRequested),
+        );
+
+        if status != EventStatus::Captured {
+            self.handle_must_close();
+        }
     }
 
     fn handle_must_close(&mut self) {
@@ -283,8 +832,52 @@ impl EventLoop {
             Event::Window(WindowEvent::WillClose),
         );
 
+        self.save_clipboard();
+
         self.event_loop_running = false;
     }
+
+    /// The `CLIPBOARD` selection stops existing when the window that owns it closes, which happens
+    /// all the time with plugin editors. If a clipboard manager is running, we'll give it a chance
+    /// to copy the clipboard's contents before the window gets destroyed. The other windows keep
+    /// running in the meantime, and the window is hidden so it doesn't look like it's hanging.
+    fn save_clipboard(&mut self) {
+        // The handler may have copied something in response to `WillClose`
+        self.claim_pending_copies();
+
+        let xcb_connection = &self.window.xcb_connection;
+        let time = self.window.last_input_time.get();
+        match self.window.clipboard.borrow_mut().start_save(xcb_connection, time) {
+            Ok(true) => (),
+            _ => return,
+        }
+
+        let _ = xcb_connection.conn.unmap_window(self.window.window_id);
+        let _ = xcb_connection.conn.flush();
+
+        self.clipboard_save_deadline = Some(Instant::now() + CLIPBOARD_SAVE_TIMEOUT);
+    }
+
+    /// Serve the clipboard manager's requests for the clipboard's contents, until it lets us know
+    /// that it's done.
+    fn handle_clipboard_save_event(&mut self, event: &XEvent) {
+        let xcb_connection = &self.window.xcb_connection;
+        let mut clipboard = self.window.clipboard.borrow_mut();
+        match event {
+            XEvent::SelectionRequest(request) => {
+                let _ = clipboard.handle_selection_request(xcb_connection, request);
+            }
+            XEvent::PropertyNotify(notify) => {
+                let _ = clipboard.handle_property_notify(xcb_connection, notify);
+            }
+            XEvent::SelectionNotify(notify)
+                if clipboard.is_save_finished(xcb_connection, notify) =>
+            {
+                self.clipboard_save_deadline = None;
+            }
+            _ => {}
+        }
+    }
 }
 
 fn mouse_id(id: u8) -> MouseButton {
@@ -0,0 +1,138 @@
+use crate::Icon;
+
+/// The sizes taskbars, docks and window switchers commonly ask for. Icons are published at each of
+/// these sizes that are smaller than the icon itself, so the window manager doesn't need to do any
+/// scaling of its own.
+const ICON_SIZES: [u32; 7] = [16, 24, 32, 48, 64, 128, 256];
+
+/// Encode the icon as the value of a `_NET_WM_ICON` property: a list of images, each consisting of
+/// its width and height followed by its pixels in ARGB order. The largest versions of the icon are
+/// left out if they would make the property longer than `max_len` values, as the X server rejects
+/// requests over its maximum request length.
+pub(super) fn net_wm_icon(icon: &Icon, max_len: usize) -> Vec<u32> {
+    let largest_side = icon.width().max(icon.height());
+    let sizes = ICON_SIZES
+        .iter()
+        .copied()
+        .filter(|&size| size < largest_side)
+        .map(|size| {
+            (
+                (icon.width() * size / largest_side).max(1),
+                (icon.height() * size / largest_side).max(1),
+            )
+        })
+        .chain(std::iter::once((icon.width(), icon.height())));
+
+    let mut data = Vec::new();
+    for (width, height) in sizes {
+        if data.len() + 2 + (width as usize * height as usize) > max_len {
+            break;
+        }
+
+        data.push(width);
+        data.push(height);
+        scale_to_argb(icon, width, height, &mut data);
+    }
+
+    data
+}
+
+/// Scale the icon down to `width` by `height` pixels by averaging the pixels that end up in the
+/// same spot, and append the result to `data` as ARGB values. The color channels are weighted by
+/// their alpha so transparent pixels don't bleed into the edges. When the size doesn't change this
+/// only converts the pixels.
+fn scale_to_argb(icon: &Icon, width: u32, height: u32, data: &mut Vec<u32>) {
+    let (src_width, src_height) = (icon.width() as usize, icon.height() as usize);
+    let (width, height) = (width as usize, height as usize);
+    let rgba = icon.rgba();
+
+    for y in 0..height {
+        let (src_y_start, src_y_end) = (y * src_height / height, (y + 1) * src_height / height);
+        for x in 0..width {
+            let (src_x_start, src_x_end) = (x * src_width / width, (x + 1) * src_width / width);
+
+            // The sums of the alpha weighted color channels, and the sum of the alpha channel
+            let mut color_sums = [0u64; 3];
+            let mut alpha_sum = 0u64;
+            for src_y in src_y_start..src_y_end {
+                for src_x in src_x_start..src_x_end {
+                    let pixel = &rgba[(src_y * src_width + src_x) * 4..][..4];
+                    let alpha = u64::from(pixel[3]);
+                    for (sum, &channel) in color_sums.iter_mut().zip(&pixel[..3]) {
+                        *sum += u64::from(channel) * alpha;
+                    }
+                    alpha_sum += alpha;
+                }
+            }
+
+            let pixel_count = ((src_y_end - src_y_start) * (src_x_end - src_x_start)) as u64;
+            let average_color = |sum: u64| (sum + alpha_sum / 2) / alpha_sum.max(1);
+            let [red, green, blue] = color_sums.map(average_color);
+            let alpha = (alpha_sum + pixel_count / 2) / pixel_count;
+
+            data.push((alpha << 24 | red << 16 | green << 8 | blue) as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sizes of the images in a `_NET_WM_ICON` value.
+    fn image_sizes(mut data: &[u32]) -> Vec<(u32, u32)> {
+        let mut sizes = Vec::new();
+        while let [width, height, rest @ ..] = data {
+            sizes.push((*width, *height));
+            data = &rest[(width * height) as usize..];
+        }
+
+        sizes
+    }
+
+    #[test]
+    fn pixels_are_argb() {
+        let icon = Icon::from_rgba(1, 1, vec![0x11, 0x22, 0x33, 0x44]);
+
+        assert_eq!(net_wm_icon(&icon, usize::MAX), [1, 1, 0x44112233]);
+    }
+
+    #[test]
+    fn smaller_sizes_keep_the_aspect_ratio() {
+        let icon = Icon::from_rgba(300, 200, vec![0; 300 * 200 * 4]);
+
+        assert_eq!(
+            image_sizes(&net_wm_icon(&icon, usize::MAX)),
+            [(16, 10), (24, 16), (32, 21), (48, 32), (64, 42), (128, 85), (256, 170), (300, 200)]
+        );
+    }
+
+    #[test]
+    fn downscaling_weighs_colors_by_alpha() {
+        let mut data = Vec::new();
+
+        // Transparent pixels don't contribute their color
+        let icon = Icon::from_rgba(2, 1, vec![0xff, 0, 0, 0xff, 0, 0, 0xff, 0]);
+        scale_to_argb(&icon, 1, 1, &mut data);
+        assert_eq!(data, [0x80ff0000]);
+
+        // Opaque pixels are averaged evenly
+        data.clear();
+        let icon = Icon::from_rgba(2, 1, vec![0xff, 0, 0, 0xff, 0, 0, 0xff, 0xff]);
+        scale_to_argb(&icon, 1, 1, &mut data);
+        assert_eq!(data, [0xff800080]);
+    }
+
+    #[test]
+    fn large_sizes_are_left_out_when_too_long() {
+        let icon = Icon::from_rgba(32, 32, vec![0; 32 * 32 * 4]);
+
+        // The 16x16 and 24x24 images fit, but the full size 32x32 image doesn't
+        let max_len = (2 + 16 * 16) + (2 + 24 * 24) + (2 + 32 * 32) - 1;
+        let data = net_wm_icon(&icon, max_len);
+        assert_eq!(image_sizes(&data), [(16, 16), (24, 24)]);
+        assert_eq!(data.len(), (2 + 16 * 16) + (2 + 24 * 24));
+
+        assert!(net_wm_icon(&icon, 2 + 16 * 16 - 1).is_empty());
+    }
+}
@@ -4,7 +4,18 @@ use xcb_connection::XcbConnection;
 mod window;
 pub use window::*;
 
+mod application;
+pub use application::Application;
+mod embedded;
+pub use embedded::EmbeddedWindow;
+mod clipboard;
+pub use clipboard::{copy_data_to_clipboard, copy_to_clipboard, copy_to_primary_selection};
+
 mod cursor;
+mod dispatcher;
+mod drag_drop;
 mod event_loop;
+mod icon;
 mod keyboard;
 mod visual_info;
+mod waker;
@@ -1,5 +1,5 @@
 use crate::x11::xcb_connection::XcbConnection;
-use std::error::Error;
+use crate::Error;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
     Colormap, ColormapAlloc, ConnectionExt, Screen, VisualClass, Visualid,
@@ -20,14 +20,14 @@ impl WindowVisualConfig {
     #[cfg(feature = "opengl")]
     pub fn find_best_visual_config_for_gl(
         connection: &XcbConnection, gl_config: Option<crate::gl::GlConfig>,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, Error> {
         let Some(gl_config) = gl_config else { return Self::find_best_visual_config(connection) };
 
         // SAFETY: TODO
         let (fb_config, window_config) = unsafe {
             crate::gl::platform::GlContext::get_fb_config_and_visual(connection.dpy, gl_config)
         }
-        .expect("Could not fetch framebuffer config");
+        .map_err(Error::GlCreationFailed)?;
 
         Ok(Self {
             fb_config: Some(fb_config),
@@ -37,7 +37,7 @@ impl WindowVisualConfig {
         })
     }
 
-    pub fn find_best_visual_config(connection: &XcbConnection) -> Result<Self, Box<dyn Error>> {
+    pub fn find_best_visual_config(connection: &XcbConnection) -> Result<Self, Error> {
         match find_visual_for_depth(connection.screen(), 32) {
             None => Ok(Self::copy_from_parent()),
             Some(visual_id) => Ok(Self {
@@ -63,16 +63,15 @@ impl WindowVisualConfig {
 
 // For this 32-bit depth to work, you also need to define a color map and set a border
 // pixel: https://cgit.freedesktop.org/xorg/xserver/tree/dix/window.c#n818
-fn create_color_map(
-    connection: &XcbConnection, visual_id: Visualid,
-) -> Result<Colormap, Box<dyn Error>> {
-    let colormap = connection.conn.generate_id()?;
-    connection.conn.create_colormap(
-        ColormapAlloc::NONE,
-        colormap,
-        connection.screen().root,
-        visual_id,
-    )?;
+fn create_color_map(connection: &XcbConnection, visual_id: Visualid) -> Result<Colormap, Error> {
+    let colormap = connection
+        .conn
+        .generate_id()
+        .map_err(|err| Error::VisualNegotiationFailed(err.to_string()))?;
+    connection
+        .conn
+        .create_colormap(ColormapAlloc::NONE, colormap, connection.screen().root, visual_id)
+        .map_err(|err| Error::VisualNegotiationFailed(err.to_string()))?;
 
     Ok(colormap)
 }
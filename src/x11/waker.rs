@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::os::unix::io::RawFd;
+
+use nix::sys::eventfd::{eventfd, EfdFlags};
+
+/// Wakes up a window's event loop from another thread. The event loop polls this eventfd along
+/// with the X11 connection, so it can block indefinitely while nothing needs to be drawn.
+pub(crate) struct Waker {
+    fd: RawFd,
+}
+
+impl Waker {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let fd = eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)?;
+
+        Ok(Self { fd })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Make the event loop's `poll()` call return.
+    pub fn wake(&self) {
+        let _ = nix::unistd::write(self.fd, &1u64.to_ne_bytes());
+    }
+
+    /// Called by the event loop after it has been woken up, so the next `poll()` call blocks again.
+    pub fn reset(&self) {
+        let mut buf = [0u8; 8];
+        let _ = nix::unistd::read(self.fd, &mut buf);
+    }
+}
+
+impl Drop for Waker {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.fd);
+    }
+}
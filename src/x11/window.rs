@@ -1,52 +1,127 @@
-use std::cell::Cell;
-use std::error::Error;
+use std::cell::{Cell, RefCell};
 use std::ffi::c_void;
+use std::fmt::Display;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
-use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, XlibDisplayHandle,
     XlibWindowHandle,
 };
 
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::ConnectionError;
+use x11rb::properties::{AspectRatio, WmSizeHints, WmSizeHintsSpecification};
+use x11rb::protocol::randr::{ConnectionExt as _, MonitorInfo};
 use x11rb::protocol::xproto::{
     AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, CreateGCAux,
-    CreateWindowAux, EventMask, PropMode, Visualid, Window as XWindow, WindowClass,
+    CreateWindowAux, EventMask, InputFocus, PropMode, Timestamp, Visualid, Window as XWindow,
+    WindowClass,
 };
 use x11rb::wrapper::ConnectionExt as _;
-
+use x11rb::CURRENT_TIME;
+
+use super::clipboard::{latin1_from_utf8, Clipboard};
+use super::dispatcher::{self, Dispatcher};
+use super::drag_drop::{DragSource, DropTarget, XDND_VERSION};
+use super::embedded::EmbeddedWindow;
+use super::icon::net_wm_icon;
+use super::waker::Waker;
 use super::XcbConnection;
 use crate::{
-    Event, MouseCursor, Size, WindowEvent, WindowHandler, WindowInfo, WindowOpenOptions,
-    WindowScalePolicy,
+    ClipboardFormat, DragData, DropEffect, Error, Event, FrameClock, Icon, MouseCursor, PhyPoint,
+    PhySize, Point, RedrawPolicy, Size, UserEvent, WindowEvent, WindowHandler, WindowInfo,
+    WindowOpenOptions, WindowPosition, WindowScalePolicy,
 };
 
 #[cfg(feature = "opengl")]
 use crate::gl::{platform, GlContext};
 use crate::x11::event_loop::EventLoop;
 use crate::x11::visual_info::WindowVisualConfig;
+use crate::WindowTask;
 
 pub struct WindowHandle {
     raw_window_handle: Option<RawWindowHandle>,
-    event_loop_handle: Option<JoinHandle<()>>,
     close_requested: Arc<AtomicBool>,
     is_open: Arc<AtomicBool>,
+    /// Disconnects once the window has closed. `None` for embedded windows, as those only close
+    /// while the host is pumping their events.
+    closed: Option<mpsc::Receiver<()>>,
+    /// Whether the window's events are pumped by the host, on the thread this handle lives on.
+    embedded: bool,
+    event_proxy: EventProxy,
 }
 
 impl WindowHandle {
     pub fn close(&mut self) {
         self.close_requested.store(true, Ordering::Relaxed);
-        if let Some(event_loop) = self.event_loop_handle.take() {
-            let _ = event_loop.join();
+        self.event_proxy.waker.wake();
+        self.wait_until_closed();
+    }
+
+    /// Block until the window has closed. When called from one of the window handlers, the window
+    /// closes after the handler returns, so this can't wait for that to happen.
+    fn wait_until_closed(&mut self) {
+        if let Some(closed) = self.closed.take() {
+            if !dispatcher::is_dispatcher_thread() {
+                let _ = closed.recv();
+            }
         }
     }
 
     pub fn is_open(&self) -> bool {
         self.is_open.load(Ordering::Relaxed)
     }
+
+    pub fn event_proxy(&self) -> EventProxy {
+        self.event_proxy.clone()
+    }
+
+    pub fn run_on_window_thread(&self, task: WindowTask) -> bool {
+        self.event_proxy.send(WindowMessage::Run(task))
+    }
+
+    pub fn is_on_window_thread(&self) -> bool {
+        self.embedded || dispatcher::is_dispatcher_thread()
+    }
+}
+
+#[derive(Clone)]
+pub struct EventProxy {
+    sender: mpsc::Sender<WindowMessage>,
+    pub(crate) waker: Arc<Waker>,
+}
+
+impl EventProxy {
+    fn new() -> Result<(Self, mpsc::Receiver<WindowMessage>), Box<dyn std::error::Error>> {
+        let (sender, receiver) = mpsc::channel();
+        let waker = Arc::new(Waker::new()?);
+
+        Ok((Self { sender, waker }, receiver))
+    }
+
+    pub fn send_event(&self, event: UserEvent) -> bool {
+        self.send(WindowMessage::User(event))
+    }
+
+    fn send(&self, message: WindowMessage) -> bool {
+        // This fails once the window's event loop has exited and dropped the receiver
+        if self.sender.send(message).is_err() {
+            return false;
+        }
+
+        self.waker.wake();
+        true
+    }
+}
+
+/// Messages sent to the window's event loop from other threads.
+pub(crate) enum WindowMessage {
+    User(UserEvent),
+    Run(WindowTask),
 }
 
 unsafe impl HasRawWindowHandle for WindowHandle {
@@ -64,20 +139,40 @@ unsafe impl HasRawWindowHandle for WindowHandle {
 pub(crate) struct ParentHandle {
     close_requested: Arc<AtomicBool>,
     is_open: Arc<AtomicBool>,
+    /// The event proxy shared with the window handle, along with its receiving end. The latter is
+    /// taken by the window when it gets created.
+    event_proxy: EventProxy,
+    messages: Option<mpsc::Receiver<WindowMessage>>,
+    /// Dropped along with the window, which lets the window handle know that it has closed.
+    _closed: mpsc::Sender<()>,
 }
 
 impl ParentHandle {
-    pub fn new() -> (Self, WindowHandle) {
+    /// Fails if the event proxy's eventfd could not be created, which only happens when running
+    /// out of file descriptors.
+    pub fn new() -> Result<(Self, WindowHandle), Error> {
         let close_requested = Arc::new(AtomicBool::new(false));
         let is_open = Arc::new(AtomicBool::new(true));
+        let (event_proxy, messages) = EventProxy::new().map_err(creation_failed)?;
+        let (closed_tx, closed_rx) = mpsc::channel();
         let handle = WindowHandle {
             raw_window_handle: None,
-            event_loop_handle: None,
             close_requested: Arc::clone(&close_requested),
             is_open: Arc::clone(&is_open),
+            closed: Some(closed_rx),
+            embedded: false,
+            event_proxy: event_proxy.clone(),
         };
 
-        (Self { close_requested, is_open }, handle)
+        let parent_handle = Self {
+            close_requested,
+            is_open,
+            event_proxy,
+            messages: Some(messages),
+            _closed: closed_tx,
+        };
+
+        Ok((parent_handle, handle))
     }
 
     pub fn parent_did_drop(&self) -> bool {
@@ -96,15 +191,169 @@ pub(crate) struct WindowInner {
     #[cfg(feature = "opengl")]
     gl_context: Option<GlContext>,
 
-    pub(crate) xcb_connection: XcbConnection,
-    window_id: XWindow,
+    pub(crate) xcb_connection: Rc<XcbConnection>,
+    pub(crate) window_id: XWindow,
     pub(crate) window_info: WindowInfo,
     visual_id: Visualid,
     mouse_cursor: Cell<MouseCursor>,
 
     pub(crate) close_requested: Cell<bool>,
+
+    /// The window the window was created in. That's the root window for top-level windows.
+    parent_id: XWindow,
+    /// The physical position of the top left corner of the window's frame, relative to the parent
+    /// window.
+    pub(crate) position: Cell<PhyPoint>,
+    /// The offset from the top left corner of the window's frame to the window itself, from the
+    /// window manager's `_NET_FRAME_EXTENTS`. This is zero for child windows.
+    pub(crate) frame_offset: Cell<PhyPoint>,
+    size_constraints: Cell<SizeConstraints>,
+
+    pub(crate) frame_interval: Cell<Duration>,
+    pub(crate) frame_clock: FrameClock,
+    pub(crate) redraw_policy: RedrawPolicy,
+    pub(crate) redraw_requested: Cell<bool>,
+    /// Keeps the [`Application`][crate::Application] this window belongs to running, if any.
+    /// Windows opened from this window's handler belong to the same application.
+    application: Option<mpsc::Sender<()>>,
+    /// Used to wake up the event loop and to send it user events from other threads.
+    pub(crate) event_proxy: EventProxy,
+    pub(crate) messages: mpsc::Receiver<WindowMessage>,
+
+    pub(crate) has_focus: Cell<bool>,
+    /// The timestamp of the last key or button event, for requests that shouldn't use
+    /// `CurrentTime`.
+    pub(crate) last_input_time: Cell<Timestamp>,
+
+    pub(crate) clipboard: RefCell<Clipboard>,
+    pub(crate) drop_target: RefCell<DropTarget>,
+    pub(crate) drag_source: RefCell<DragSource>,
 }
 
+impl Drop for WindowInner {
+    fn drop(&mut self) {
+        // The connection is shared with the other windows, so the window needs to be destroyed
+        // explicitly. Its OpenGL context needs to go first.
+        #[cfg(feature = "opengl")]
+        drop(self.gl_context.take());
+
+        // Other windows may still be sending data to the same requestors
+        let _ = self.clipboard.get_mut().cancel_transfers(&self.xcb_connection);
+
+        let _ = self.xcb_connection.conn.destroy_window(self.window_id);
+        let _ = self.xcb_connection.conn.flush();
+    }
+}
+
+impl WindowInner {
+    /// Ask the X server where the window's frame currently is.
+    pub(crate) fn query_position(&self) -> Result<PhyPoint, Box<dyn std::error::Error>> {
+        let origin = self
+            .xcb_connection
+            .conn
+            .translate_coordinates(self.window_id, self.parent_id, 0, 0)?
+            .reply()?;
+        let frame_offset = self.frame_offset.get();
+
+        Ok(PhyPoint::new(
+            i32::from(origin.dst_x) - frame_offset.x,
+            i32::from(origin.dst_y) - frame_offset.y,
+        ))
+    }
+
+    /// Read the size of the decorations to the left of and above the window from
+    /// `_NET_FRAME_EXTENTS`, which the window manager sets on top-level windows.
+    pub(crate) fn query_frame_offset(&self) -> Result<PhyPoint, Box<dyn std::error::Error>> {
+        let reply = self
+            .xcb_connection
+            .conn
+            .get_property(
+                false,
+                self.window_id,
+                self.xcb_connection.atoms._NET_FRAME_EXTENTS,
+                AtomEnum::CARDINAL,
+                0,
+                4,
+            )?
+            .reply()?;
+
+        // The extents are stored as left, right, top, bottom
+        let extents: Vec<u32> = reply.value32().map(Iterator::collect).unwrap_or_default();
+        match extents[..] {
+            [left, _, top, _] => Ok(PhyPoint::new(left as i32, top as i32)),
+            _ => Ok(PhyPoint::new(0, 0)),
+        }
+    }
+
+    fn set_size_constraints(&self, size_constraints: SizeConstraints) {
+        self.size_constraints.set(size_constraints);
+
+        let size_hints = size_constraints.size_hints(&self.window_info);
+        let _ = size_hints.set_normal_hints(&self.xcb_connection.conn, self.window_id);
+        let _ = self.xcb_connection.conn.flush();
+    }
+
+    /// Give the window keyboard focus. `time` should be the timestamp of the event that caused
+    /// this, as the X server will ignore focus changes that are older than the last one.
+    pub(crate) fn set_input_focus(&self, time: Timestamp) {
+        let _ = self.xcb_connection.conn.set_input_focus(InputFocus::PARENT, self.window_id, time);
+        let _ = self.xcb_connection.conn.flush();
+    }
+}
+
+/// The limits on how the user can resize a top-level window, in logical pixels.
+#[derive(Clone, Copy)]
+struct SizeConstraints {
+    resizable: bool,
+    min_size: Option<Size>,
+    max_size: Option<Size>,
+    aspect_ratio: Option<f64>,
+}
+
+impl SizeConstraints {
+    fn new(options: &WindowOpenOptions) -> Self {
+        Self {
+            resizable: options.resizable,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            aspect_ratio: options.aspect_ratio,
+        }
+    }
+
+    /// The ICCCM `WM_NORMAL_HINTS` for these constraints, for a window with the given size and
+    /// scale.
+    fn size_hints(&self, window_info: &WindowInfo) -> WmSizeHints {
+        let to_physical = |size: PhySize| (size.width as i32, size.height as i32);
+        let from_logical = |size: Size| {
+            to_physical(WindowInfo::from_logical_size(size, window_info.scale()).physical_size())
+        };
+
+        let mut size_hints = WmSizeHints::new();
+        if self.resizable {
+            size_hints.min_size = self.min_size.map(from_logical);
+            size_hints.max_size = self.max_size.map(from_logical);
+        } else {
+            // Window managers don't allow resizing windows that can only have a single size
+            let size = to_physical(window_info.physical_size());
+            size_hints.min_size = Some(size);
+            size_hints.max_size = Some(size);
+        }
+
+        // The hints store the aspect ratio as a fraction, with the same minimum and maximum
+        size_hints.aspect = self.aspect_ratio.filter(|ratio| *ratio > 0.0).map(|ratio| {
+            let numerator = (ratio * ASPECT_RATIO_DENOMINATOR as f64).round() as i32;
+            let aspect_ratio = AspectRatio::new(numerator, ASPECT_RATIO_DENOMINATOR);
+
+            (aspect_ratio, aspect_ratio)
+        });
+
+        size_hints
+    }
+}
+
+/// The denominator used to store aspect ratios in the size hints.
+const ASPECT_RATIO_DENOMINATOR: i32 = 10_000;
+
 pub struct Window<'a> {
     pub(crate) inner: &'a WindowInner,
 }
@@ -114,7 +363,7 @@ struct SendableRwh(RawWindowHandle);
 
 unsafe impl Send for SendableRwh {}
 
-type WindowOpenResult = Result<SendableRwh, ()>;
+type WindowOpenResult = Result<SendableRwh, Error>;
 
 impl<'a> Window<'a> {
     pub fn open_parented<P, H, B>(parent: &P, options: WindowOpenOptions, build: B) -> WindowHandle
@@ -124,68 +373,160 @@ impl<'a> Window<'a> {
         B: FnOnce(&mut crate::Window) -> H,
         B: Send + 'static,
     {
-        // Convert parent into something that X understands
-        let parent_id = match parent.raw_window_handle() {
-            RawWindowHandle::Xlib(h) => h.window as u32,
-            RawWindowHandle::Xcb(h) => h.window,
-            h => panic!("unsupported parent handle type {:?}", h),
-        };
+        Self::try_open_parented::<P, H, B>(parent, options, build).unwrap()
+    }
 
-        let (tx, rx) = mpsc::sync_channel::<WindowOpenResult>(1);
-        let (parent_handle, mut window_handle) = ParentHandle::new();
-        let join_handle = thread::spawn(move || {
-            Self::window_thread(Some(parent_id), options, build, tx.clone(), Some(parent_handle))
-                .unwrap();
-        });
+    pub fn try_open_parented<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        let parent_id = parent_window_id(parent)?;
+
+        let (parent_handle, mut window_handle) = ParentHandle::new()?;
+        let raw_window_handle =
+            Self::open(Some(parent_id), None, options, build, parent_handle, None)?;
 
-        let raw_window_handle = rx.recv().unwrap().unwrap();
         window_handle.raw_window_handle = Some(raw_window_handle.0);
-        window_handle.event_loop_handle = Some(join_handle);
-        window_handle
+        Ok(window_handle)
     }
 
-    pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
+    /// Open a child window on this thread, with its own connection to the X server. Instead of
+    /// being run by the dispatcher thread, the window's event loop is driven by the host through
+    /// the returned [`EmbeddedWindow`].
+    pub fn open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> (WindowHandle, EmbeddedWindow)
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+    {
+        Self::try_open_parented_embedded::<P, H, B>(parent, options, build).unwrap()
+    }
+
+    pub fn try_open_parented_embedded<P, H, B>(
+        parent: &P, options: WindowOpenOptions, build: B,
+    ) -> Result<(WindowHandle, EmbeddedWindow), Error>
+    where
+        P: HasRawWindowHandle,
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+    {
+        let parent_id = parent_window_id(parent)?;
+
+        let (parent_handle, mut window_handle) = ParentHandle::new()?;
+        let waker = Arc::clone(&parent_handle.event_proxy.waker);
+        let embedded_window =
+            EmbeddedWindow::new(Dispatcher::new()?, waker).map_err(creation_failed)?;
+        let raw_window_handle = Self::create(
+            embedded_window.dispatcher(),
+            Some(parent_id),
+            None,
+            options,
+            build,
+            parent_handle,
+            None,
+        )?;
+
+        window_handle.raw_window_handle = Some(raw_window_handle);
+        window_handle.closed = None;
+        window_handle.embedded = true;
+        Ok((window_handle, embedded_window))
+    }
+
+    /// Open a top-level window without waiting for it to close. `application` is stored in the
+    /// window, and dropped when it closes. `owner` is the window the new window was opened from,
+    /// if any.
+    pub(crate) fn open_top_level<H, B>(
+        options: WindowOpenOptions, build: B, application: Option<mpsc::Sender<()>>,
+        owner: Option<XWindow>,
+    ) -> Result<WindowHandle, Error>
     where
         H: WindowHandler + 'static,
         B: FnOnce(&mut crate::Window) -> H,
         B: Send + 'static,
     {
-        let (tx, rx) = mpsc::sync_channel::<WindowOpenResult>(1);
+        let (parent_handle, mut window_handle) = ParentHandle::new()?;
+        let raw_window_handle =
+            Self::open(None, owner, options, build, parent_handle, application)?;
 
-        let thread = thread::spawn(move || {
-            Self::window_thread(None, options, build, tx, None).unwrap();
-        });
+        window_handle.raw_window_handle = Some(raw_window_handle.0);
+        Ok(window_handle)
+    }
 
-        let _ = rx.recv().unwrap().unwrap();
+    pub fn open_blocking<H, B>(options: WindowOpenOptions, build: B)
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        Self::try_open_blocking::<H, B>(options, build).unwrap()
+    }
 
-        thread.join().unwrap_or_else(|err| {
-            eprintln!("Window thread panicked: {:#?}", err);
-        });
+    pub fn try_open_blocking<H, B>(options: WindowOpenOptions, build: B) -> Result<(), Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        // The window handle is only used to wait for the window to close. When this is called from
+        // another window's handler the window is opened alongside that window instead.
+        let (parent_handle, mut window_handle) = ParentHandle::new()?;
+        Self::open(None, None, options, build, parent_handle, None)?;
+
+        window_handle.wait_until_closed();
+        Ok(())
     }
 
-    fn window_thread<H, B>(
-        parent: Option<u32>, options: WindowOpenOptions, build: B,
-        tx: mpsc::SyncSender<WindowOpenResult>, parent_handle: Option<ParentHandle>,
-    ) -> Result<(), Box<dyn Error>>
+    /// Create the window on the dispatcher thread, where all windows' event loops run.
+    fn open<H, B>(
+        parent: Option<u32>, owner: Option<XWindow>, options: WindowOpenOptions, build: B,
+        parent_handle: ParentHandle, application: Option<mpsc::Sender<()>>,
+    ) -> WindowOpenResult
     where
         H: WindowHandler + 'static,
         B: FnOnce(&mut crate::Window) -> H,
         B: Send + 'static,
     {
-        // Connect to the X server
-        // FIXME: baseview error type instead of unwrap()
-        let xcb_connection = XcbConnection::new()?;
+        dispatcher::run(move |dispatcher| {
+            Self::create(dispatcher, parent, owner, options, build, parent_handle, application)
+                .map(SendableRwh)
+        })
+        .and_then(|result| result)
+    }
+
+    fn create<H, B>(
+        dispatcher: &Dispatcher, parent: Option<u32>, owner: Option<XWindow>,
+        options: WindowOpenOptions, build: B, mut parent_handle: ParentHandle,
+        application: Option<mpsc::Sender<()>>,
+    ) -> Result<RawWindowHandle, Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+    {
+        let xcb_connection = Rc::clone(dispatcher.connection());
 
         // Get screen information
         let screen = xcb_connection.screen();
-        let parent_id = parent.unwrap_or(screen.root);
-
-        let gc_id = xcb_connection.conn.generate_id()?;
-        xcb_connection.conn.create_gc(
-            gc_id,
-            parent_id,
-            &CreateGCAux::new().foreground(screen.black_pixel).graphics_exposures(0),
-        )?;
+        let parent_id = match parent {
+            // An invalid parent would otherwise only result in an asynchronous X11 error after the
+            // window has failed to be created
+            Some(parent_id) => {
+                let geometry =
+                    xcb_connection.conn.get_geometry(parent_id).map_err(creation_failed)?.reply();
+                if geometry.is_err() {
+                    return Err(Error::InvalidParent);
+                }
+
+                parent_id
+            }
+            None => screen.root,
+        };
 
         let scaling = match options.scale {
             WindowScalePolicy::SystemScaleFactor => xcb_connection.get_scaling().unwrap_or(1.0),
@@ -193,80 +534,65 @@ impl<'a> Window<'a> {
         };
 
         let window_info = WindowInfo::from_logical_size(options.size, scaling);
+        let frame_interval = options.frame_interval();
+        let size_constraints = SizeConstraints::new(&options);
+
+        let event_proxy = parent_handle.event_proxy.clone();
+        let messages = parent_handle.messages.take().unwrap();
 
         #[cfg(feature = "opengl")]
-        let visual_info =
-            WindowVisualConfig::find_best_visual_config_for_gl(&xcb_connection, options.gl_config)?;
+        let visual_info = WindowVisualConfig::find_best_visual_config_for_gl(
+            &xcb_connection,
+            options.gl_config.clone(),
+        )?;
 
         #[cfg(not(feature = "opengl"))]
         let visual_info = WindowVisualConfig::find_best_visual_config(&xcb_connection)?;
 
-        let window_id = xcb_connection.conn.generate_id()?;
-        xcb_connection.conn.create_window(
-            visual_info.visual_depth,
-            window_id,
+        // Top-level windows are only placed where we want them if the position is set in the hints
+        let center_on = if parent.is_none() { owner } else { None };
+        let position =
+            initial_position(&xcb_connection, options.position, parent_id, center_on, &window_info)
+                .map_err(creation_failed)?;
+        let mut size_hints = size_constraints.size_hints(&window_info);
+        size_hints.position = position
+            .map(|position| (WmSizeHintsSpecification::UserSpecified, position.x, position.y));
+
+        let position = position.unwrap_or(PhyPoint::new(0, 0));
+        let window_id = create_x_window(
+            &xcb_connection,
             parent_id,
-            0,                                         // x coordinate of the new window
-            0,                                         // y coordinate of the new window
-            window_info.physical_size().width as u16,  // window width
-            window_info.physical_size().height as u16, // window height
-            0,                                         // window border
-            WindowClass::INPUT_OUTPUT,
-            visual_info.visual_id,
-            &CreateWindowAux::new()
-                .event_mask(
-                    EventMask::EXPOSURE
-                        | EventMask::POINTER_MOTION
-                        | EventMask::BUTTON_PRESS
-                        | EventMask::BUTTON_RELEASE
-                        | EventMask::KEY_PRESS
-                        | EventMask::KEY_RELEASE
-                        | EventMask::STRUCTURE_NOTIFY
-                        | EventMask::ENTER_WINDOW
-                        | EventMask::LEAVE_WINDOW,
-                )
-                // As mentioned above, these two values are needed to be able to create a window
-                // with a depth of 32-bits when the parent window has a different depth
-                .colormap(visual_info.color_map)
-                .border_pixel(0),
-        )?;
-        xcb_connection.conn.map_window(window_id)?;
-
-        // Change window title
-        let title = options.title;
-        xcb_connection.conn.change_property8(
-            PropMode::REPLACE,
-            window_id,
-            AtomEnum::WM_NAME,
-            AtomEnum::STRING,
-            title.as_bytes(),
-        )?;
-
-        xcb_connection.conn.change_property32(
-            PropMode::REPLACE,
-            window_id,
-            xcb_connection.atoms.WM_PROTOCOLS,
-            AtomEnum::ATOM,
-            &[xcb_connection.atoms.WM_DELETE_WINDOW],
-        )?;
-
-        xcb_connection.conn.flush()?;
-
-        // TODO: These APIs could use a couple tweaks now that everything is internal and there is
-        //       no error handling anymore at this point. Everything is more or less unchanged
-        //       compared to when raw-gl-context was a separate crate.
+            position,
+            &window_info,
+            &visual_info,
+            &options,
+            &size_hints,
+        )
+        .map_err(creation_failed)?;
+
+        // TODO: These APIs could use a couple tweaks now that everything is internal. Everything
+        //       is more or less unchanged compared to when raw-gl-context was a separate crate.
         #[cfg(feature = "opengl")]
-        let gl_context = visual_info.fb_config.map(|fb_config| {
-            use std::ffi::c_ulong;
-
-            let window = window_id as c_ulong;
-            let display = xcb_connection.dpy;
-
-            // Because of the visual negotation we had to take some extra steps to create this context
-            let context = unsafe { platform::GlContext::create(window, display, fb_config) }
-                .expect("Could not create OpenGL context");
-            GlContext::new(context)
-        });
+        let gl_context = visual_info
+            .fb_config
+            .map(|fb_config| {
+                use std::os::raw::c_ulong;
+
+                let window = window_id as c_ulong;
+                let display = xcb_connection.dpy;
+
+                // Because of the visual negotation we had to take some extra steps to create this context
+                unsafe { platform::GlContext::create(window, display, fb_config) }
+                    .map(GlContext::new)
+            })
+            .transpose()
+            .map_err(|err| {
+                // Nothing owns the window yet, so it needs to be cleaned up here
+                let _ = xcb_connection.conn.destroy_window(window_id);
+                let _ = xcb_connection.conn.flush();
+
+                Error::GlCreationFailed(err)
+            })?;
 
         let mut inner = WindowInner {
             xcb_connection,
@@ -277,6 +603,27 @@ impl<'a> Window<'a> {
 
             close_requested: Cell::new(false),
 
+            parent_id,
+            position: Cell::new(position),
+            frame_offset: Cell::new(PhyPoint::new(0, 0)),
+            size_constraints: Cell::new(size_constraints),
+
+            frame_interval: Cell::new(frame_interval),
+            frame_clock: options.frame_clock,
+            redraw_policy: options.redraw_policy,
+            // The window needs to be drawn at least once
+            redraw_requested: Cell::new(true),
+            application,
+            event_proxy,
+            messages,
+
+            has_focus: Cell::new(false),
+            last_input_time: Cell::new(CURRENT_TIME),
+
+            clipboard: RefCell::new(Clipboard::new(window_id)),
+            drop_target: RefCell::new(DropTarget::new(window_id)),
+            drag_source: RefCell::new(DragSource::new(window_id)),
+
             #[cfg(feature = "opengl")]
             gl_context,
         };
@@ -289,11 +636,84 @@ impl<'a> Window<'a> {
         // the correct dpi scaling.
         handler.on_event(&mut window, Event::Window(WindowEvent::Resized(window_info)));
 
-        let _ = tx.send(Ok(SendableRwh(window.raw_window_handle())));
+        let raw_window_handle = window.raw_window_handle();
 
-        EventLoop::new(inner, handler, parent_handle).run()?;
+        dispatcher.add_window(EventLoop::new(inner, handler, Some(parent_handle)));
 
-        Ok(())
+        Ok(raw_window_handle)
+    }
+
+    pub fn open_window<H, B>(&mut self, options: WindowOpenOptions, build: B) -> WindowHandle
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        self.try_open_window::<H, B>(options, build).unwrap()
+    }
+
+    pub fn try_open_window<H, B>(
+        &mut self, options: WindowOpenOptions, build: B,
+    ) -> Result<WindowHandle, Error>
+    where
+        H: WindowHandler + 'static,
+        B: FnOnce(&mut crate::Window) -> H,
+        B: Send + 'static,
+    {
+        Self::open_top_level(
+            options,
+            build,
+            self.inner.application.clone(),
+            Some(self.inner.window_id),
+        )
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        let _ = set_title(&self.inner.xcb_connection, self.inner.window_id, title);
+        let _ = self.inner.xcb_connection.conn.flush();
+    }
+
+    pub fn set_icon(&mut self, icon: &Icon) {
+        let _ = set_icon(&self.inner.xcb_connection, self.inner.window_id, icon);
+        let _ = self.inner.xcb_connection.conn.flush();
+    }
+
+    pub fn position(&self) -> Point {
+        self.inner.position.get().to_logical(&self.inner.window_info)
+    }
+
+    pub fn set_position(&mut self, position: Point) {
+        // Window managers move the frame's top left corner to this position, as per ICCCM
+        let position = position.to_physical(&self.inner.window_info);
+        let _ = self.inner.xcb_connection.conn.configure_window(
+            self.inner.window_id,
+            &ConfigureWindowAux::new().x(position.x).y(position.y),
+        );
+        let _ = self.inner.xcb_connection.conn.flush();
+
+        // This will trigger a `ConfigureNotify` event, after which the handler receives a
+        // `WindowEvent::Moved` event
+    }
+
+    pub fn set_resizable(&mut self, resizable: bool) {
+        let size_constraints = SizeConstraints { resizable, ..self.inner.size_constraints.get() };
+        self.inner.set_size_constraints(size_constraints);
+    }
+
+    pub fn set_min_size(&mut self, min_size: Option<Size>) {
+        let size_constraints = SizeConstraints { min_size, ..self.inner.size_constraints.get() };
+        self.inner.set_size_constraints(size_constraints);
+    }
+
+    pub fn set_max_size(&mut self, max_size: Option<Size>) {
+        let size_constraints = SizeConstraints { max_size, ..self.inner.size_constraints.get() };
+        self.inner.set_size_constraints(size_constraints);
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<f64>) {
+        let size_constraints =
+            SizeConstraints { aspect_ratio, ..self.inner.size_constraints.get() };
+        self.inner.set_size_constraints(size_constraints);
     }
 
     pub fn set_mouse_cursor(&self, mouse_cursor: MouseCursor) {
@@ -318,18 +738,71 @@ impl<'a> Window<'a> {
         self.inner.close_requested.set(true);
     }
 
+    pub fn event_proxy(&self) -> EventProxy {
+        self.inner.event_proxy.clone()
+    }
+
+    pub fn request_redraw(&mut self) {
+        self.inner.redraw_requested.set(true);
+    }
+
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.inner.frame_interval.set(interval);
+    }
+
     pub fn has_focus(&mut self) -> bool {
-        unimplemented!()
+        self.inner.has_focus.get()
     }
 
     pub fn focus(&mut self) {
-        unimplemented!()
+        self.inner.set_input_focus(self.inner.last_input_time.get());
+    }
+
+    pub fn request_clipboard_text(&mut self) {
+        let time = self.inner.last_input_time.get();
+        self.inner.clipboard.borrow_mut().request_text(&self.inner.xcb_connection, time);
+    }
+
+    pub fn request_clipboard_data(&mut self, format: ClipboardFormat) {
+        let time = self.inner.last_input_time.get();
+        let mut clipboard = self.inner.clipboard.borrow_mut();
+        clipboard.request_data(&self.inner.xcb_connection, format, time);
+    }
+
+    pub fn request_clipboard_formats(&mut self) {
+        let time = self.inner.last_input_time.get();
+        self.inner.clipboard.borrow_mut().request_formats(&self.inner.xcb_connection, time);
+    }
+
+    pub fn request_primary_selection_text(&mut self) {
+        let time = self.inner.last_input_time.get();
+        let mut clipboard = self.inner.clipboard.borrow_mut();
+        clipboard.request_primary_text(&self.inner.xcb_connection, time);
+    }
+
+    pub fn start_drag(&mut self, data: DragData, allowed_effects: &[DropEffect]) {
+        let mut clipboard = self.inner.clipboard.borrow_mut();
+        let _ = self.inner.drag_source.borrow_mut().start(
+            &self.inner.xcb_connection,
+            &mut clipboard,
+            data,
+            allowed_effects,
+            self.inner.last_input_time.get(),
+        );
     }
 
     pub fn resize(&mut self, size: Size) {
         let scaling = self.inner.window_info.scale();
         let new_window_info = WindowInfo::from_logical_size(size, scaling);
 
+        // A window that isn't resizable has its size pinned by the size hints
+        let size_constraints = self.inner.size_constraints.get();
+        if !size_constraints.resizable {
+            let size_hints = size_constraints.size_hints(&new_window_info);
+            let _ =
+                size_hints.set_normal_hints(&self.inner.xcb_connection.conn, self.inner.window_id);
+        }
+
         let _ = self.inner.xcb_connection.conn.configure_window(
             self.inner.window_id,
             &ConfigureWindowAux::new()
@@ -348,6 +821,224 @@ impl<'a> Window<'a> {
     }
 }
 
+/// Create and map the X11 window, and set the properties the window manager and other clients look
+/// at.
+fn create_x_window(
+    xcb_connection: &XcbConnection, parent_id: XWindow, position: PhyPoint,
+    window_info: &WindowInfo, visual_info: &WindowVisualConfig, options: &WindowOpenOptions,
+    size_hints: &WmSizeHints,
+) -> Result<XWindow, Box<dyn std::error::Error>> {
+    let screen = xcb_connection.screen();
+
+    let gc_id = xcb_connection.conn.generate_id()?;
+    xcb_connection.conn.create_gc(
+        gc_id,
+        parent_id,
+        &CreateGCAux::new().foreground(screen.black_pixel).graphics_exposures(0),
+    )?;
+
+    let window_id = xcb_connection.conn.generate_id()?;
+    xcb_connection.conn.create_window(
+        visual_info.visual_depth,
+        window_id,
+        parent_id,
+        position.x as i16,                         // x coordinate of the new window
+        position.y as i16,                         // y coordinate of the new window
+        window_info.physical_size().width as u16,  // window width
+        window_info.physical_size().height as u16, // window height
+        0,                                         // window border
+        WindowClass::INPUT_OUTPUT,
+        visual_info.visual_id,
+        &CreateWindowAux::new()
+            .event_mask(
+                EventMask::EXPOSURE
+                    | EventMask::POINTER_MOTION
+                    | EventMask::BUTTON_PRESS
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::KEY_PRESS
+                    | EventMask::KEY_RELEASE
+                    | EventMask::STRUCTURE_NOTIFY
+                    | EventMask::PROPERTY_CHANGE
+                    | EventMask::ENTER_WINDOW
+                    | EventMask::LEAVE_WINDOW
+                    | EventMask::FOCUS_CHANGE,
+            )
+            // As mentioned above, these two values are needed to be able to create a window
+            // with a depth of 32-bits when the parent window has a different depth
+            .colormap(visual_info.color_map)
+            .border_pixel(0),
+    )?;
+
+    // The window manager reads these when the window gets mapped
+    size_hints.set_normal_hints(&xcb_connection.conn, window_id)?;
+    xcb_connection.conn.map_window(window_id)?;
+
+    xcb_connection.select_clipboard_changes(window_id)?;
+
+    set_title(xcb_connection, window_id, &options.title)?;
+    if let Some(icon) = &options.icon {
+        set_icon(xcb_connection, window_id, icon)?;
+    }
+
+    xcb_connection.conn.change_property32(
+        PropMode::REPLACE,
+        window_id,
+        xcb_connection.atoms.WM_PROTOCOLS,
+        AtomEnum::ATOM,
+        &[xcb_connection.atoms.WM_DELETE_WINDOW, xcb_connection.atoms.WM_TAKE_FOCUS],
+    )?;
+
+    // Let other clients know that files can be dropped onto this window
+    xcb_connection.conn.change_property32(
+        PropMode::REPLACE,
+        window_id,
+        xcb_connection.atoms.XdndAware,
+        AtomEnum::ATOM,
+        &[XDND_VERSION],
+    )?;
+
+    xcb_connection.conn.flush()?;
+
+    Ok(window_id)
+}
+
+/// Where to place a new window within `parent_id`, if anywhere in particular. `owner` is the
+/// window to center the window on, if it isn't the parent.
+fn initial_position(
+    xcb_connection: &XcbConnection, position: WindowPosition, parent_id: XWindow,
+    owner: Option<XWindow>, window_info: &WindowInfo,
+) -> Result<Option<PhyPoint>, Box<dyn std::error::Error>> {
+    let center_on = match position {
+        WindowPosition::Default => return Ok(None),
+        WindowPosition::Point(position) => return Ok(Some(position.to_physical(window_info))),
+        WindowPosition::CenteredOnScreen => parent_id,
+        WindowPosition::CenteredOnParent => owner.unwrap_or(parent_id),
+    };
+
+    // The area to center the window in, relative to the parent. The root window spans all of the
+    // monitors, so top-level windows are centered on a single monitor instead.
+    let monitor = if center_on == xcb_connection.screen().root {
+        current_monitor(xcb_connection, owner)?
+    } else {
+        None
+    };
+    let (x, y, width, height) = match monitor {
+        Some(monitor) => (monitor.x, monitor.y, monitor.width, monitor.height),
+        None => {
+            let geometry = xcb_connection.conn.get_geometry(center_on)?.reply()?;
+            let origin =
+                xcb_connection.conn.translate_coordinates(center_on, parent_id, 0, 0)?.reply()?;
+
+            (origin.dst_x, origin.dst_y, geometry.width, geometry.height)
+        }
+    };
+
+    let size = window_info.physical_size();
+    Ok(Some(PhyPoint::new(
+        i32::from(x) + (i32::from(width) - size.width as i32) / 2,
+        i32::from(y) + (i32::from(height) - size.height as i32) / 2,
+    )))
+}
+
+/// The monitor that holds the center of the `owner` window, or the mouse pointer if there's no
+/// owner. Falls back to the primary monitor if neither is on a monitor. Returns `None` if RandR
+/// 1.5 isn't available.
+fn current_monitor(
+    xcb_connection: &XcbConnection, owner: Option<XWindow>,
+) -> Result<Option<MonitorInfo>, Box<dyn std::error::Error>> {
+    if !xcb_connection.has_randr {
+        return Ok(None);
+    }
+
+    let conn = &xcb_connection.conn;
+    let root = xcb_connection.screen().root;
+    let (x, y) = match owner {
+        Some(owner) => {
+            let geometry = conn.get_geometry(owner)?.reply()?;
+            let origin = conn.translate_coordinates(owner, root, 0, 0)?.reply()?;
+
+            (
+                i32::from(origin.dst_x) + i32::from(geometry.width) / 2,
+                i32::from(origin.dst_y) + i32::from(geometry.height) / 2,
+            )
+        }
+        None => {
+            let pointer = conn.query_pointer(root)?.reply()?;
+
+            (i32::from(pointer.root_x), i32::from(pointer.root_y))
+        }
+    };
+
+    let mut monitors = conn.randr_get_monitors(root, true)?.reply()?.monitors;
+    let contains_point = |monitor: &MonitorInfo| {
+        (i32::from(monitor.x)..i32::from(monitor.x) + i32::from(monitor.width)).contains(&x)
+            && (i32::from(monitor.y)..i32::from(monitor.y) + i32::from(monitor.height)).contains(&y)
+    };
+    let index = monitors
+        .iter()
+        .position(contains_point)
+        .or_else(|| monitors.iter().position(|monitor| monitor.primary))
+        .unwrap_or(0);
+
+    Ok((index < monitors.len()).then(|| monitors.swap_remove(index)))
+}
+
+/// Set the window's title. Window managers that follow EWMH read the UTF-8 `_NET_WM_NAME`, and the
+/// ICCCM `WM_NAME` is only used as a fallback. The latter is Latin-1, so characters outside of that
+/// range are replaced.
+fn set_title(
+    xcb_connection: &XcbConnection, window_id: XWindow, title: &str,
+) -> Result<(), ConnectionError> {
+    xcb_connection.conn.change_property8(
+        PropMode::REPLACE,
+        window_id,
+        xcb_connection.atoms._NET_WM_NAME,
+        xcb_connection.atoms.UTF8_STRING,
+        title.as_bytes(),
+    )?;
+
+    let latin1_title = latin1_from_utf8(title.as_bytes());
+    xcb_connection.conn.change_property8(
+        PropMode::REPLACE,
+        window_id,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        &latin1_title,
+    )?;
+
+    Ok(())
+}
+
+/// Set the window's `_NET_WM_ICON`, which contains the icon at several sizes.
+fn set_icon(
+    xcb_connection: &XcbConnection, window_id: XWindow, icon: &Icon,
+) -> Result<(), ConnectionError> {
+    // The `ChangeProperty` request's header takes up six of the request's four byte units
+    let max_len = xcb_connection.conn.maximum_request_bytes() / 4 - 6;
+    xcb_connection.conn.change_property32(
+        PropMode::REPLACE,
+        window_id,
+        xcb_connection.atoms._NET_WM_ICON,
+        AtomEnum::CARDINAL,
+        &net_wm_icon(icon, max_len),
+    )?;
+
+    Ok(())
+}
+
+fn creation_failed<E: Display>(err: E) -> Error {
+    Error::WindowCreationFailed(err.to_string())
+}
+
+/// Convert the parent's handle into something that X understands.
+fn parent_window_id<P: HasRawWindowHandle>(parent: &P) -> Result<u32, Error> {
+    match parent.raw_window_handle() {
+        RawWindowHandle::Xlib(h) => Ok(h.window as u32),
+        RawWindowHandle::Xcb(h) => Ok(h.window),
+        _ => Err(Error::InvalidParent),
+    }
+}
+
 unsafe impl<'a> HasRawWindowHandle for Window<'a> {
     fn raw_window_handle(&self) -> RawWindowHandle {
         let mut handle = XlibWindowHandle::empty();
@@ -370,7 +1061,3 @@ unsafe impl<'a> HasRawDisplayHandle for Window<'a> {
         RawDisplayHandle::Xlib(handle)
     }
 }
-
-pub fn copy_to_clipboard(_data: &str) {
-    todo!()
-}
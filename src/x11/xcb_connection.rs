@@ -1,12 +1,19 @@
 use std::cell::RefCell;
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
 use std::error::Error;
 
 use x11::{xlib, xlib::Display, xlib_xcb};
 
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection, RequestConnection};
 use x11rb::cursor::Handle as CursorHandle;
-use x11rb::protocol::xproto::{Cursor, Screen};
+use x11rb::protocol::present::{self, ConnectionExt as _};
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xfixes::{self, ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, ChangeWindowAttributesAux, ConnectionExt as _, Cursor, EventMask, Screen,
+    Window as XWindow,
+};
 use x11rb::resource_manager;
 use x11rb::xcb_ffi::XCBConnection;
 
@@ -18,12 +25,49 @@ x11rb::atom_manager! {
     pub Atoms: AtomsCookie {
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
+        _NET_WM_NAME,
+        _NET_WM_ICON,
+        _NET_FRAME_EXTENTS,
+
+        // Selections
+        CLIPBOARD,
+        CLIPBOARD_MANAGER,
+        SAVE_TARGETS,
+        TARGETS,
+        MULTIPLE,
+        INCR,
+        TEXT,
+        UTF8_STRING,
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        URI_LIST: b"text/uri-list",
+        IMAGE_PNG: b"image/png",
+        // The property on our own windows that selection owners write converted data to
+        BASEVIEW_SELECTION,
+
+        // Drag and drop
+        XdndAware,
+        XdndEnter,
+        XdndPosition,
+        XdndStatus,
+        XdndLeave,
+        XdndDrop,
+        XdndFinished,
+        XdndSelection,
+        XdndTypeList,
+        XdndActionCopy,
+        XdndActionMove,
+        XdndActionLink,
+        XdndActionList,
+        // The property on our own windows that drag sources write the dragged data to
+        BASEVIEW_DRAG_DATA,
     }
 }
 
 /// A very light abstraction around the XCB connection.
 ///
-/// Keeps track of the xcb connection itself and the xlib display ID that was used to connect.
+/// Keeps track of the xcb connection itself and the xlib display ID that was used to connect. All
+/// windows in the process share a single connection.
 pub struct XcbConnection {
     pub(crate) dpy: *mut Display,
     pub(crate) conn: XCBConnection,
@@ -32,14 +76,33 @@ pub struct XcbConnection {
     pub(crate) resources: resource_manager::Database,
     pub(crate) cursor_handle: CursorHandle,
     pub(super) cursor_cache: RefCell<HashMap<MouseCursor, u32>>,
+    /// Whether the XFixes extension is available, which we need to be notified of selection
+    /// owner changes.
+    pub(crate) has_xfixes: bool,
+    /// Whether the Present extension is available, which we use to sync frames to the display.
+    pub(crate) has_present: bool,
+    /// Whether RandR 1.5 is available, which we need to find out where the individual monitors
+    /// are.
+    pub(crate) has_randr: bool,
+    /// The windows created through this connection.
+    pub(crate) windows: RefCell<HashSet<XWindow>>,
+    /// The number of `INCR` transfers in progress to each of the other clients' windows we're
+    /// listening to property changes on. Transfers from different windows may go to the same
+    /// requestor, so we can only stop listening once all of them have finished.
+    incr_requestors: RefCell<HashMap<XWindow, usize>>,
 }
 
 impl XcbConnection {
     pub fn new() -> Result<Self, Box<dyn Error>> {
         let dpy = unsafe { xlib::XOpenDisplay(std::ptr::null()) };
-        assert!(!dpy.is_null());
+        if dpy.is_null() {
+            return Err("Could not open the X display".into());
+        }
         let xcb_connection = unsafe { xlib_xcb::XGetXCBConnection(dpy) };
-        assert!(!xcb_connection.is_null());
+        if xcb_connection.is_null() {
+            unsafe { xlib::XCloseDisplay(dpy) };
+            return Err("Could not get the display's XCB connection".into());
+        }
         let screen = unsafe { xlib::XDefaultScreen(dpy) } as usize;
         let conn = unsafe { XCBConnection::from_raw_xcb_connection(xcb_connection, false)? };
         unsafe {
@@ -50,6 +113,17 @@ impl XcbConnection {
         let resources = resource_manager::new_from_default(&conn)?;
         let cursor_handle = CursorHandle::new(&conn, screen, &resources)?.reply()?;
 
+        // XFixes requests can only be used after the version has been negotiated
+        let has_xfixes = conn.extension_information(xfixes::X11_EXTENSION_NAME)?.is_some()
+            && conn.xfixes_query_version(5, 0)?.reply().is_ok();
+        let has_present = conn.extension_information(present::X11_EXTENSION_NAME)?.is_some()
+            && conn.present_query_version(1, 0)?.reply().is_ok();
+        let has_randr = conn.extension_information(randr::X11_EXTENSION_NAME)?.is_some()
+            && conn
+                .randr_query_version(1, 5)?
+                .reply()
+                .map_or(false, |version| (version.major_version, version.minor_version) >= (1, 5));
+
         Ok(Self {
             dpy,
             conn,
@@ -58,6 +132,11 @@ impl XcbConnection {
             resources,
             cursor_handle,
             cursor_cache: RefCell::new(HashMap::new()),
+            has_xfixes,
+            has_present,
+            has_randr,
+            windows: RefCell::new(HashSet::new()),
+            incr_requestors: RefCell::new(HashMap::new()),
         })
     }
 
@@ -118,6 +197,79 @@ impl XcbConnection {
         }
     }
 
+    /// Ask the X server to notify `window` through XFixes selection events whenever the owner of
+    /// the `CLIPBOARD` selection changes. This does nothing if XFixes is not available.
+    pub fn select_clipboard_changes(&self, window: XWindow) -> Result<(), Box<dyn Error>> {
+        if self.has_xfixes {
+            self.conn.xfixes_select_selection_input(
+                window,
+                self.atoms.CLIPBOARD,
+                SelectionEventMask::SET_SELECTION_OWNER
+                    | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                    | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `window` is one of our own windows. The event masks on these windows must be left
+    /// alone, since we can only select a single set of events per window.
+    pub fn is_own_window(&self, window: XWindow) -> bool {
+        self.windows.borrow().contains(&window)
+    }
+
+    /// Listen for property changes on `requestor` for an `INCR` transfer to it. Every call needs to
+    /// be paired with a call to [`release_requestor()`][Self::release_requestor()] once the
+    /// transfer has ended.
+    pub fn select_requestor(&self, requestor: XWindow) -> Result<(), Box<dyn Error>> {
+        // Our own windows already select property changes
+        if self.is_own_window(requestor) {
+            return Ok(());
+        }
+
+        let mut requestors = self.incr_requestors.borrow_mut();
+        match requestors.entry(requestor) {
+            Entry::Occupied(mut entry) => *entry.get_mut() += 1,
+            Entry::Vacant(entry) => {
+                self.conn.change_window_attributes(
+                    requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )?;
+                entry.insert(1);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop listening for property changes on `requestor` once no more `INCR` transfers to it are
+    /// in progress.
+    pub fn release_requestor(&self, requestor: XWindow) -> Result<(), Box<dyn Error>> {
+        let mut requestors = self.incr_requestors.borrow_mut();
+        if let Entry::Occupied(mut entry) = requestors.entry(requestor) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+                self.conn.change_window_attributes(
+                    requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn intern_atom(&self, name: &str) -> Result<Atom, Box<dyn Error>> {
+        Ok(self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+    }
+
+    pub fn atom_name(&self, atom: Atom) -> Result<String, Box<dyn Error>> {
+        let reply = self.conn.get_atom_name(atom)?.reply()?;
+        Ok(String::from_utf8_lossy(&reply.name).into_owned())
+    }
+
     pub fn screen(&self) -> &Screen {
         &self.conn.setup().roots[self.screen]
     }